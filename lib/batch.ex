@@ -0,0 +1,71 @@
+defmodule ExTypst.Batch do
+  @moduledoc """
+  A batch of documents being streamed to a sink process, see
+  `ExTypst.stream_pdfs/3`.
+
+  The sink receives one message per document, in order:
+
+      {:ex_typst_batch, id, index, {:ok, pdf} | {:error, reason}}
+
+  and the next document is only compiled once the sink acknowledged the
+  previous one with `ack/1`, so slow sinks (e.g. uploads) are never flooded.
+  After the last acknowledgment the sink receives `{:ex_typst_batch, id, :done}`,
+  or `{:ex_typst_batch, id, :cancelled}` if `ExTypst.shutdown/1` cancelled the
+  batch before it finished.
+
+  If the batch struct is garbage collected, the batch stops after the
+  document waiting for its acknowledgment.
+  """
+
+  @enforce_keys [:id, :ref]
+  defstruct [:id, :ref]
+
+  @type t :: %__MODULE__{id: pos_integer(), ref: reference()}
+
+  @type result :: {:ok, binary()} | {:error, ExTypst.error()}
+
+  @spec ack(t()) :: :ok
+  @doc """
+  Acknowledges the last document received, allowing the next one to be
+  produced.
+
+  Each document takes exactly one acknowledgment: acknowledging it again, or
+  before the next document arrived, has no effect.
+  """
+  def ack(%__MODULE__{ref: ref}), do: ExTypst.NIF.batch_ack(ref)
+
+  @spec each(t(), (non_neg_integer(), result() -> any()), timeout()) ::
+          :ok | {:error, :timeout | :cancelled}
+  @doc """
+  Consumes the batch in the calling process, which must be its sink.
+
+  `fun` is called with the index and result of every document, and the
+  document is acknowledged once it returns. Returns `{:error, :timeout}` if
+  no message arrives within `timeout` milliseconds, and `{:error, :cancelled}`
+  if the batch was cancelled by a shutdown.
+
+  ## Examples
+
+      iex> {:ok, batch} = ExTypst.stream_pdfs(["= One", "= Two"])
+      iex> ExTypst.Batch.each(batch, fn index, {:ok, pdf} -> send(self(), {index, is_binary(pdf)}) end)
+      :ok
+      iex> receive do: (message -> message)
+      {0, true}
+  """
+  def each(%__MODULE__{id: id} = batch, fun, timeout \\ :infinity) do
+    receive do
+      {:ex_typst_batch, ^id, index, result} ->
+        fun.(index, result)
+        ack(batch)
+        each(batch, fun, timeout)
+
+      {:ex_typst_batch, ^id, :done} ->
+        :ok
+
+      {:ex_typst_batch, ^id, :cancelled} ->
+        {:error, :cancelled}
+    after
+      timeout -> {:error, :timeout}
+    end
+  end
+end
@@ -11,8 +11,23 @@ defmodule ExTypst do
 
   @embedded_fonts [Path.join(:code.priv_dir(:ex_typst), "fonts")]
 
+  # Default font configuration to ensure consistent rendering
+  @font_defaults """
+  #set text(
+    font: ("Times New Roman", "Times", "Liberation Serif", "DejaVu Serif", "serif"),
+    size: 11pt,
+    lang: "en"
+  )
+
+  """
+
   @type formattable :: {atom, any}
 
+  @typedoc """
+  A persistent compilation environment, see `new_world/1`.
+  """
+  @opaque world :: reference()
+
   @spec render_to_string(String.t(), list(formattable)) :: String.t()
   @doc """
   Formats the given markup template with the given bindings, mostly 
@@ -27,17 +42,142 @@ defmodule ExTypst do
     EEx.eval_string(typst_markup, bindings)
   end
 
-  @type pdf_opt :: {:extra_fonts, list(String.t())} | {:use_font_defaults, boolean()}
+  @type permission :: :print | :copy | :modify | :annotate
+
+  @type encrypt_opt ::
+          {:owner_password, String.t()}
+          | {:user_password, String.t()}
+          | {:deny, list(permission())}
+
+  @type requirement ::
+          {:min_pages, non_neg_integer()}
+          | {:labels, list(String.t())}
+          | {:text, list(String.t())}
+
+  @type pdf_opt ::
+          {:extra_fonts, list(String.t())}
+          | {:root, Path.t()}
+          | {:use_font_defaults, boolean()}
+          | {:world, world()}
+          | {:encrypt, list(encrypt_opt())}
+          | {:max_pages, pos_integer()}
+          | {:reject_empty, boolean()}
+          | {:expect_uniform_pages, boolean()}
+          | {:assertions, boolean()}
+          | {:debug, boolean()}
+          | {:require, list(requirement())}
+
+  @typedoc """
+  Why a document couldn't be built: a compilation or export error message,
+  `:shutdown` after `shutdown/1` was called, `{:internal, message}` if the
+  compiler crashed (which leaves the node and any world in use intact),
+  `{:assertion_failed, failures}` if the only errors were failed assertions
+  of the template (see the `:assertions` option), or a failed check requested
+  by the options.
+  """
+  @type error ::
+          String.t()
+          | :shutdown
+          | {:internal, String.t()}
+          | :empty_document
+          | {:page_limit, pos_integer()}
+          | {:assertion_failed, list(assertion_failure())}
+          | {:mixed_page_sizes, list(page_size())}
+          | {:validation, list(validation_failure())}
+          | {:feature_disabled, :svg_export | :png_export}
+
+  @type page_size :: %{
+          page: pos_integer(),
+          width: float(),
+          height: float(),
+          orientation: :portrait | :landscape
+        }
+
+  @type assertion_failure :: %{message: String.t(), position: source_position() | nil}
+
+  @typedoc """
+  A decision made while resolving a file or a font, see the `:debug` option
+  of `render_to_pdf/3`.
+  """
+  @type log_event :: %{
+          kind: :file | :font,
+          path: String.t(),
+          outcome: :memory | :read | :cache_hit | :loaded | :failed,
+          detail: String.t() | nil
+        }
+
+  @type validation_failure ::
+          {:min_pages, non_neg_integer(), non_neg_integer()}
+          | {:missing_label, String.t()}
+          | {:missing_text, String.t()}
 
   @spec render_to_pdf(String.t(), list(formattable), list(pdf_opt)) ::
-          {:ok, binary()} | {:error, String.t()}
+          {:ok, binary()}
+          | {:error, error()}
+          | {:ok, binary(), list(log_event())}
+          | {:error, error(), list(log_event())}
   @doc """
   Converts a given piece of typst markup to a PDF binary.
 
   ## Options
 
   * `:extra_fonts` - List of additional font paths to use
+  * `:root` - Directory that files not passed in memory (e.g. images) are
+    read from. Without it, templates can't read files from disk.
   * `:use_font_defaults` - Whether to apply default font settings for consistent rendering (default: true)
+  * `:world` - Compile inside a world created by `new_world/1` instead of a
+    fresh one, keeping the document around for `trace/4`. `:extra_fonts` and
+    `:root` are ignored in that case, as they are fixed on the world's creation.
+  * `:encrypt` - Password-protects the PDF (with AES-128), given as a keyword
+    list with:
+    * `:owner_password` - Password granting full access (required)
+    * `:user_password` - Password needed to open the document (default: `""`,
+      i.e. anyone can open it, subject to the permissions)
+    * `:deny` - Actions denied to readers who only know the user password,
+      any of `:print`, `:copy`, `:modify` and `:annotate` (default: `[]`)
+  * `:max_pages` - Fails with `{:error, {:page_limit, max_pages}}` when the
+    document has more pages, before it is checked any further or exported.
+    typst can't stop layout midway, so a runaway template is still laid out
+    completely, but the cost of exporting it (usually the larger part) is
+    avoided. Without a limit, documents can have any number of pages.
+  * `:reject_empty` - Fails with `{:error, :empty_document}` instead of
+    producing a blank PDF when the markup is empty or whitespace-only, or when
+    no page of the document has any visible content (default: false)
+  * `:expect_uniform_pages` - Fails with `{:error, {:mixed_page_sizes,
+    sizes}}` when not all pages have the same size (and thus orientation),
+    with `sizes` as returned by `page_sizes/3` (default: false)
+  * `:assertions` - Makes functions available to all files of the template
+    that fail the compilation unless its invariants hold (default: false):
+    * `assert-eq(left, right, message: ..)` - Checks that two values are
+      equal
+    * `assert-nonempty(value, message: ..)` - Checks that a value is neither
+      `none` nor an empty string, array, dictionary or content
+
+    Failed assertions are returned as `{:error, {:assertion_failed,
+    failures}}` with the message and template position of each, as long as
+    there are no other errors. typst's own `assert` functions fail like any
+    other compile error.
+  * `:debug` - Also returns a log of how files and fonts were resolved during
+    the compilation, as `{:ok, pdf, log}` or `{:error, reason, log}`
+    (default: false). Each event has the `:kind` of what was resolved
+    (`:file` or `:font`), the `:path` it was requested by, the `:outcome`
+    and a `:detail`:
+    * `:memory` - A file was passed in memory
+    * `:read` - A file was read from disk, from the path in `:detail`
+    * `:cache_hit` - A file or font was already loaded, see `new_world/1`
+    * `:loaded` - A font was loaded, with its family in `:detail`
+    * `:failed` - A file or font couldn't be loaded, with the reason in
+      `:detail` (e.g. the path that was tried)
+  * `:require` - Checks the laid out document before exporting it, failing
+    with `{:error, {:validation, failures}}` listing every unmet requirement.
+    Given as a keyword list with:
+    * `:min_pages` - Minimum number of pages, failing with
+      `{:min_pages, expected, actual}`
+    * `:labels` - Labels that must be present (without angle brackets),
+      failing with `{:missing_label, label}` for each missing one
+    * `:text` - Snippets that must appear in the text, failing with
+      `{:missing_text, snippet}` for each missing one. Differences in
+      whitespace, including line breaks, are ignored.
 
   ## Examples
 
@@ -51,27 +191,346 @@ defmodule ExTypst do
     
   """
   def render_to_pdf(typst_markup, bindings \\ [], opts \\ []) do
-    extra_fonts = Keyword.get(opts, :extra_fonts, []) ++ @embedded_fonts
-    use_defaults = Keyword.get(opts, :use_font_defaults, true)
-
     markup = render_to_string(typst_markup, bindings)
+    compile(%{"MARKUP.typ" => markup}, "MARKUP.typ", opts)
+  end
 
-    # Add default font configuration to ensure consistent rendering
-    full_markup =
-      if use_defaults do
-        """
-        #set text(
-          font: ("Times New Roman", "Times", "Liberation Serif", "DejaVu Serif", "serif"),
-          size: 11pt,
-          lang: "en"
-        )
+  @type external_images_opt :: {:min_size, non_neg_integer()} | {:url_prefix, String.t()}
 
-        """ <> markup
-      else
-        markup
-      end
+  @type svg_opt :: {:external_images, list(external_images_opt())} | pdf_opt
+
+  @type svg_export :: %{pages: list(String.t()), assets: %{String.t() => binary()}}
+
+  @spec render_to_svg(String.t(), list(formattable), list(svg_opt)) ::
+          {:ok, svg_export()} | {:error, error()}
+  @doc """
+  Converts a given piece of typst markup to SVGs, one for each page.
+
+  Returns the pages along with the assets they refer to by name, which are
+  only ever produced with the `:external_images` option.
+
+  Returns `{:error, {:feature_disabled, :svg_export}}` if the NIF was built
+  without the `svg` feature, see `supported_features/0`.
+
+  ## Options
+
+  * `:external_images` - Instead of inlining images into the pages as
+    base64-encoded data, refer to them as external assets, keeping pages
+    small enough to update them quickly in a browser. Assets are named by the
+    hash of their data, so an image used on several pages only needs to be
+    transferred once. Given as a keyword list with:
+    * `:min_size` - Size in bytes from which on images become external
+      assets, smaller ones are still inlined (default: `0`)
+    * `:url_prefix` - Prepended to the names of the assets to refer to them
+      from the pages, e.g. the URL they are served under (default: `""`)
+
+  Also accepts the same options as `tables/3`.
+
+  ## Examples
+
+      iex> {:ok, %{pages: [page]}} = ExTypst.render_to_svg("= Hey <%= name %>!", name: "Jude")
+      iex> String.starts_with?(page, "<svg")
+      true
+  """
+  def render_to_svg(typst_markup, bindings \\ [], opts \\ []) do
+    report(typst_markup, bindings, opts, {:svg, svg_options(opts)})
+  end
+
+  @type preview_opt :: {:page_format, :svg | :png} | {:ppi, number()} | pdf_opt
+
+  @spec preview_bundle(String.t(), list(formattable), list(preview_opt)) ::
+          {:ok, String.t()} | {:error, error()}
+  @doc """
+  Converts a given piece of typst markup to a JSON bundle for previewing it
+  in a browser, with all parts of the bundle coming from the same compilation.
+
+  The bundle is an object with:
+
+  * `pages` - The pages, each with:
+    * `width` and `height` - The size of the page
+    * `svg` or `png` - The page as an SVG, or as a base64-encoded PNG
+    * `links` - The links on the page, each with its `x`, `y`, `width` and
+      `height`, and either the `url` or the `target` it points to
+    * `text` - The runs of text on the page, each with its `text`, the `x`
+      and `y` of the start of its baseline, its `width` and its font size as
+      `height`
+  * `outline` - The headings of the document that are part of its outline,
+    each with its `title`, `level` and position
+
+  Positions (like link targets) have a one-based `page` and an `x` and `y`.
+  Coordinates and sizes are in points, measured from the top left corner of
+  the page.
+
+  Returns `{:error, {:feature_disabled, :svg_export}}` or
+  `{:error, {:feature_disabled, :png_export}}` if the NIF was built without
+  the feature for the page format, see `supported_features/0`.
+
+  ## Options
+
+  * `:page_format` - Whether pages are rendered as `:svg` or `:png` (default:
+    `:svg`)
+  * `:ppi` - Resolution of PNG pages, in pixels per inch (default: `144`)
+
+  Also accepts the same options as `tables/3`.
+
+  ## Examples
+
+      iex> {:ok, json} = ExTypst.preview_bundle("= Intro\\nHey <%= name %>!", name: "Jude")
+      iex> %{"outline" => [outline], "pages" => [page]} = Jason.decode!(json)
+      iex> {outline["title"], outline["page"], Enum.map(page["text"], & &1["text"])}
+      {"Intro", 1, ["Intro", "Hey Jude!"]}
+  """
+  def preview_bundle(typst_markup, bindings \\ [], opts \\ []) do
+    preview = %{
+      format: Keyword.get(opts, :page_format, :svg),
+      ppi: Keyword.get(opts, :ppi, 144) / 1
+    }
+
+    report(typst_markup, bindings, opts, {:preview, preview})
+  end
+
+  @type project_opt :: {:entry, String.t()} | pdf_opt
+
+  @spec compile_project(%{String.t() => binary()}, list(project_opt)) ::
+          {:ok, binary()}
+          | {:error, error()}
+          | {:ok, binary(), list(log_event())}
+          | {:error, error(), list(log_event())}
+  @doc """
+  Converts a whole project of typst files to a PDF binary.
+
+  `files` maps paths to their contents, which can be typst sources as well as
+  any other file read by them (images, data files, ...). Files can `#import`,
+  `#include` and read each other by their paths, relative to the importing
+  file or absolute from the project root. Sources are compiled as-is, without
+  going through `EEx`.
+
+  ## Options
+
+  * `:entry` - Path of the file to compile (required)
+
+  Also accepts the options of `render_to_pdf/3`. The font defaults are
+  prepended to the entry file only.
+
+  ## Examples
+
+      iex> files = %{
+      ...>   "main.typ" => ~s(#import "lib/greet.typ": greet\\n#greet("Jude")),
+      ...>   "lib/greet.typ" => "#let greet(name) = [= Hey #name!]"
+      ...> }
+      iex> {:ok, pdf} = ExTypst.compile_project(files, entry: "main.typ")
+      iex> is_binary(pdf)
+      true
+  """
+  def compile_project(files, opts) do
+    compile(files, Keyword.fetch!(opts, :entry), opts)
+  end
+
+  @type batch_template :: String.t() | {String.t(), list(formattable)}
+
+  @spec stream_pdfs(list(batch_template), pid(), list(pdf_opt)) ::
+          {:ok, ExTypst.Batch.t()} | {:error, :shutdown}
+  @doc """
+  Converts a batch of templates to PDF binaries, streaming them one at a time
+  to `sink`.
+
+  Templates are either markup strings or `{markup, bindings}` tuples. The
+  next document is only compiled once `sink` acknowledged the previous one,
+  see `ExTypst.Batch` for the messages it receives. Accepts the same options
+  as `render_to_pdf/3`, except for `:world`.
+
+  ## Examples
+
+      iex> {:ok, batch} = ExTypst.stream_pdfs([{"= Hey <%= name %>!", name: "Jude"}])
+      iex> id = batch.id
+      iex> receive do: ({:ex_typst_batch, ^id, index, {:ok, pdf}} -> {index, is_binary(pdf)})
+      {0, true}
+  """
+  def stream_pdfs(templates, sink \\ self(), opts \\ []) do
+    markups =
+      Enum.map(templates, fn
+        {typst_markup, bindings} -> render_to_string(typst_markup, bindings)
+        typst_markup -> render_to_string(typst_markup)
+      end)
+
+    case ExTypst.NIF.batch_stream(
+           prelude(opts),
+           markups,
+           world_options(opts),
+           nif_options(opts),
+           sink
+         ) do
+      {:ok, {ref, id}} -> {:ok, %ExTypst.Batch{id: id, ref: ref}}
+      {:error, reason} -> {:error, reason}
+    end
+  end
+
+  @type table :: %{
+          table: pos_integer(),
+          page: pos_integer(),
+          rows: list(list(String.t()))
+        }
+
+  @spec tables(String.t(), list(formattable), list(pdf_opt)) ::
+          {:ok, list(table())} | {:error, error()}
+  @doc """
+  Extracts the text of the cells of the tables in the document built from the
+  given markup, by row and column.
+
+  A table broken across pages is returned in parts, one for each page, which
+  share the table's one-based number in the document. Cells covered by a cell
+  spanning several rows or columns are empty strings.
+
+  Accepts the options of `render_to_pdf/3` that affect the layout, the others
+  are ignored. With `:world`, the world keeps the document for inspection.
+
+  ## Examples
+
+      iex> ExTypst.tables("#table(columns: 2, [Item], [Price], [Tea], [<%= price %>])", price: 4)
+      {:ok, [%{table: 1, page: 1, rows: [["Item", "Price"], ["Tea", "4"]]}]}
+  """
+  def tables(typst_markup, bindings \\ [], opts \\ []) do
+    report(typst_markup, bindings, opts, :tables)
+  end
+
+  @type image :: %{
+          page: pos_integer(),
+          x: float(),
+          y: float(),
+          width: float(),
+          height: float(),
+          natural_width: float(),
+          natural_height: float(),
+          sha256: String.t(),
+          path: String.t() | nil,
+          position: source_position() | nil
+        }
+
+  @spec images(String.t(), list(formattable), list(pdf_opt)) ::
+          {:ok, list(image())} | {:error, error()}
+  @doc """
+  Lists the images placed in the document built from the given markup, in
+  the order they appear on the pages.
+
+  For each image, this returns:
+
+  * `:page`, `:x` and `:y` - Where the top left corner of the image is, in
+    points from the top left corner of the page
+  * `:width` and `:height` - Size of the image on the page, in points
+  * `:natural_width` and `:natural_height` - Size of the image itself, in
+    pixels for raster images and in points for SVGs
+  * `:sha256` - Hex-encoded SHA-256 hash of the image file
+  * `:path` - Path of the image file relative to the `:root` directory, or
+    `nil` for images created from bytes and images read by a world with
+    `cache: :none`, which doesn't keep track of the files it read
+  * `:position` - Position of the element that placed the image in the
+    template
+
+  Accepts the same options as `tables/3`.
+
+  ## Examples
+
+      iex> svg = "<svg xmlns='http://www.w3.org/2000/svg' width='20' height='10'/>"
+      iex> markup = ~s|#image(bytes("<%= svg %>"), format: "svg", width: 4cm)|
+      iex> {:ok, [image]} = ExTypst.images(markup, svg: svg)
+      iex> {image.page, image.natural_width, image.path}
+      {1, 20.0, nil}
+  """
+  def images(typst_markup, bindings \\ [], opts \\ []) do
+    report(typst_markup, bindings, opts, :images)
+  end
+
+  @type font_usage :: %{
+          family: String.t(),
+          style: :normal | :italic | :oblique,
+          weight: pos_integer(),
+          stretch: float(),
+          path: String.t() | nil,
+          index: non_neg_integer(),
+          glyphs: non_neg_integer(),
+          distinct_glyphs: non_neg_integer()
+        }
+
+  @spec font_usage(String.t(), list(formattable), list(pdf_opt)) ::
+          {:ok, list(font_usage())} | {:error, error()}
+  @doc """
+  Lists the font faces used in the document built from the given markup, in
+  the order they first appear, with the number of glyphs set in each of them
+  (`:glyphs`) and how many of those are distinct (`:distinct_glyphs`).
+
+  Faces are identified by their family and variant (`:style`, `:weight` from
+  100 to 900 and `:stretch` as a ratio of the normal width), along with the
+  `:path` of their font file and their `:index` within it. Faces nobody asked
+  for, e.g. the fallback for characters missing from the configured fonts,
+  show up just like any other.
+
+  Accepts the same options as `tables/3`.
+
+  ## Examples
+
+      iex> {:ok, [face]} = ExTypst.font_usage(~s|#set text(font: "Inria Serif")\\nabba|)
+      iex> {face.family, face.weight, face.glyphs, face.distinct_glyphs}
+      {"Inria Serif", 400, 4, 2}
+  """
+  def font_usage(typst_markup, bindings \\ [], opts \\ []) do
+    report(typst_markup, bindings, opts, :fonts)
+  end
+
+  @spec page_sizes(String.t(), list(formattable), list(pdf_opt)) ::
+          {:ok, list(page_size())} | {:error, error()}
+  @doc """
+  Returns the size of each page of the document built from the given markup,
+  in points, along with its orientation. Square pages count as portrait.
 
-    ExTypst.NIF.compile(full_markup, extra_fonts)
+  Accepts the same options as `tables/3`.
+
+  ## Examples
+
+      iex> ExTypst.page_sizes(~s|#set page(width: 200pt, height: 100pt)\\nWide|)
+      {:ok, [%{page: 1, width: 200.0, height: 100.0, orientation: :landscape}]}
+  """
+  def page_sizes(typst_markup, bindings \\ [], opts \\ []) do
+    report(typst_markup, bindings, opts, :pages)
+  end
+
+  @type metadata_opt :: {:label, String.t()} | pdf_opt
+
+  @spec query_metadata(String.t(), list(formattable), list(metadata_opt)) ::
+          {:ok, list(term())} | {:error, error()}
+  @doc """
+  Returns the values of the `metadata` elements in the document built from
+  the given markup, in document order.
+
+  Values are decoded into native terms:
+
+  * `none` and `auto` become `nil` and `:auto`
+  * booleans, integers, floats and strings stay what they are, except for
+    infinite floats and NaN, which become `:infinity`, `:neg_infinity` and
+    `:nan`
+  * decimals become `{:decimal, string}`, so that no precision is lost
+  * labels become strings, and content the string of its plain text
+  * arrays become lists, and dictionaries maps with string keys
+  * dates, times and datetimes become `Date`, `Time` and `NaiveDateTime`
+    structs
+  * all other values (lengths, colors, ...) become their typst
+    representation as a string
+
+  ## Options
+
+  * `:label` - Only returns the values of metadata with this label (without
+    angle brackets)
+
+  Also accepts the same options as `tables/3`.
+
+  ## Examples
+
+      iex> due = "datetime(year: 2024, month: 5, day: 1)"
+      iex> markup = ~s|#metadata((total: 42.5, items: 3, due: <%= due %>)) <invoice>|
+      iex> ExTypst.query_metadata(markup, [due: due], label: "invoice")
+      {:ok, [%{"total" => 42.5, "items" => 3, "due" => ~D[2024-05-01]}]}
+  """
+  def query_metadata(typst_markup, bindings \\ [], opts \\ []) do
+    report(typst_markup, bindings, opts, {:metadata, Keyword.get(opts, :label)})
   end
 
   @spec render_to_pdf!(String.t(), list(formattable)) :: binary()
@@ -81,7 +540,291 @@ defmodule ExTypst do
   def render_to_pdf!(typst_markup, bindings \\ []) do
     case render_to_pdf(typst_markup, bindings) do
       {:ok, pdf} -> pdf
-      {:error, reason} -> raise "could not build pdf: #{reason}"
+      {:error, reason} when is_binary(reason) -> raise "could not build pdf: #{reason}"
+      {:error, reason} -> raise "could not build pdf: #{inspect(reason)}"
     end
   end
+
+  @type cache_policy :: :none | :per_compile | {:persistent, ttl :: non_neg_integer()}
+
+  @type world_opt ::
+          {:extra_fonts, list(String.t())} | {:root, Path.t()} | {:cache, cache_policy()}
+
+  @spec new_world(list(world_opt)) :: {:ok, world()} | {:error, :shutdown}
+  @doc """
+  Creates a persistent world to compile documents in.
+
+  Fonts are searched once when the world is created instead of on every
+  compilation, and the last document compiled in the world (by passing it as
+  the `:world` option to `render_to_pdf/3`) is kept for inspection with
+  `trace/4`. A world compiles one document at a time.
+
+  Worlds also cache the files they read from disk. Giving each tenant (or
+  any other group of templates) its own world keeps templates that change
+  often from polluting the cache of stable ones, see `cache_stats/1`.
+
+  ## Options
+
+  * `:extra_fonts` - List of additional font paths to use
+  * `:root` - Directory that files not passed in memory are read from, see
+    `render_to_pdf/3`
+  * `:cache` - How long files read from disk are cached (default: `:per_compile`):
+    * `:none` - Files are read again on every access
+    * `:per_compile` - Files are cached for the duration of a compilation
+    * `{:persistent, ttl}` - Files are cached across compilations, until
+      they are older than `ttl` milliseconds
+  """
+  def new_world(opts \\ []) do
+    ExTypst.NIF.world_new(world_options(opts))
+  end
+
+  @type cache_stats :: %{
+          hits: non_neg_integer(),
+          misses: non_neg_integer(),
+          evictions: non_neg_integer(),
+          entries: non_neg_integer()
+        }
+
+  @spec cache_stats(world()) :: {:ok, cache_stats()} | {:error, :shutdown}
+  @doc """
+  Returns statistics of the cache of files `world` read from disk.
+
+  Hits and misses count file accesses since the world was created, while
+  evictions count files dropped from the cache because they expired or their
+  compilation ended.
+
+  ## Examples
+
+      iex> {:ok, world} = ExTypst.new_world(root: "priv", cache: {:persistent, 60_000})
+      iex> ExTypst.cache_stats(world)
+      {:ok, %{hits: 0, misses: 0, evictions: 0, entries: 0}}
+  """
+  def cache_stats(world) do
+    ExTypst.NIF.world_cache_stats(world)
+  end
+
+  @type source_position :: %{path: String.t(), line: pos_integer(), column: pos_integer()}
+
+  @spec trace(world(), pos_integer(), number(), number()) ::
+          {:ok, source_position()}
+          | {:error, :no_document | :invalid_page | :not_found | :shutdown}
+  @doc """
+  Finds the template position that produced the element at the given point
+  of the last document compiled in `world`.
+
+  `page` is one-based and `x`/`y` are measured in points from the top left
+  corner of the page. Lines are relative to the rendered markup, so the font
+  defaults prelude never shifts them.
+
+  ## Examples
+
+      iex> {:ok, world} = ExTypst.new_world()
+      iex> {:ok, _pdf} = ExTypst.render_to_pdf("Hello <%= name %>", [name: "Jude"], world: world)
+      iex> match?({:ok, %{path: "MARKUP.typ", line: 1}}, ExTypst.trace(world, 1, 75, 75))
+      true
+  """
+  def trace(world, page, x, y) do
+    ExTypst.NIF.world_trace(world, page, x / 1, y / 1)
+  end
+
+  @type warning :: %{
+          kind: :compiler | :page_overflow | :container_overflow,
+          message: String.t(),
+          page: pos_integer() | nil,
+          position: source_position() | nil
+        }
+
+  @spec warnings(world()) :: {:ok, [warning()]} | {:error, :no_document | :shutdown}
+  @doc """
+  Returns the warnings about the last document compiled in `world`.
+
+  Besides the warnings of the typst compiler, this reports content that
+  doesn't fit where it was laid out, which typst silently lets spill over:
+
+  * `:page_overflow` - Content extends beyond the edges of the page, e.g. an
+    unbreakable block taller than the page
+  * `:container_overflow` - Content exceeds the block, box or table cell it
+    is in, e.g. a long word in a table column of fixed width
+
+  Overflow is reported once per page or container, for the element exceeding
+  it the most, with `page` set to the page it's on. Text is measured along
+  its baseline, so glyphs spilling slightly over their lines don't count.
+
+  ## Examples
+
+      iex> {:ok, world} = ExTypst.new_world()
+      iex> markup = "#box(width: 1cm)[Supercalifragilistic]"
+      iex> {:ok, _pdf} = ExTypst.render_to_pdf(markup, [], world: world)
+      iex> {:ok, [warning]} = ExTypst.warnings(world)
+      iex> warning.kind
+      :container_overflow
+  """
+  def warnings(world) do
+    ExTypst.NIF.world_warnings(world)
+  end
+
+  @type features :: %{
+          typst_version: String.t(),
+          svg_export: boolean(),
+          png_export: boolean(),
+          html_export: boolean(),
+          pdf_standards: list(atom()),
+          plugins: boolean(),
+          encryption: boolean()
+        }
+
+  @spec supported_features() :: features()
+  @doc """
+  Returns what the loaded NIF binary supports.
+
+  Which typst release the NIF is built against and which exporters are
+  compiled in is chosen with cargo features (see the README), so code that
+  has to run against differently built binaries can check here first.
+
+    * `:typst_version` - the exact typst version the NIF is built against
+    * `:svg_export` - whether `render_to_svg/3` and SVG preview pages work
+    * `:png_export` - whether PNG preview pages work
+    * `:html_export` - whether documents can be exported to HTML
+    * `:pdf_standards` - the PDF standards documents can be exported in
+    * `:plugins` - whether templates can load WebAssembly plugins
+    * `:encryption` - whether PDFs can be encrypted with `:encrypt`
+
+  ## Examples
+
+      iex> ExTypst.supported_features().typst_version
+      "0.13.1"
+  """
+  def supported_features do
+    ExTypst.NIF.supported_features()
+  end
+
+  @spec shutdown(timeout :: non_neg_integer()) :: :ok | {:error, {:timeout, pos_integer()}}
+  @doc """
+  Shuts the compiler down, e.g. before stopping a node.
+
+  New work is refused with `{:error, :shutdown}` from then on. Compilations
+  and batches in flight get up to `timeout` milliseconds to finish, after
+  which batches are cancelled before their next document; compilations that
+  already started can't be interrupted and run to completion. Finally, the
+  state of all worlds is freed.
+
+  Returns `{:error, {:timeout, n}}` with the number of jobs that were still
+  running at the deadline.
+
+  There is no way to start the compiler again without restarting the node.
+  """
+  def shutdown(timeout \\ 5_000) do
+    ExTypst.NIF.shutdown(timeout)
+  end
+
+  defp compile(files, entry, opts) do
+    files = Map.to_list(files)
+
+    cond do
+      Keyword.get(opts, :debug, false) ->
+        compile_with_log(files, entry, opts)
+
+      world = opts[:world] ->
+        ExTypst.NIF.world_compile(world, prelude(opts), files, entry, nif_options(opts))
+
+      true ->
+        ExTypst.NIF.compile(prelude(opts), files, entry, world_options(opts), nif_options(opts))
+    end
+  end
+
+  # The log is kept by the world, so compile in a world of its own unless
+  # one was passed in.
+  defp compile_with_log(files, entry, opts) do
+    world =
+      case Keyword.fetch(opts, :world) do
+        {:ok, world} -> {:ok, world}
+        :error -> new_world(opts)
+      end
+
+    with {:ok, world} <- world do
+      result = ExTypst.NIF.world_compile(world, prelude(opts), files, entry, nif_options(opts))
+      with {:ok, log} <- ExTypst.NIF.world_log(world), do: Tuple.append(result, log)
+    end
+  end
+
+  defp report(typst_markup, bindings, opts, report) do
+    files = [{"MARKUP.typ", render_to_string(typst_markup, bindings)}]
+
+    case Keyword.fetch(opts, :world) do
+      {:ok, world} ->
+        ExTypst.NIF.world_compile_report(
+          world,
+          prelude(opts),
+          files,
+          "MARKUP.typ",
+          nif_options(opts),
+          report
+        )
+
+      :error ->
+        ExTypst.NIF.compile_report(
+          prelude(opts),
+          files,
+          "MARKUP.typ",
+          world_options(opts),
+          nif_options(opts),
+          report
+        )
+    end
+  end
+
+  defp svg_options(opts) do
+    %{external_images: external_images(Keyword.get(opts, :external_images))}
+  end
+
+  defp external_images(nil), do: nil
+
+  defp external_images(external) do
+    %{
+      min_size: Keyword.get(external, :min_size, 0),
+      url_prefix: Keyword.get(external, :url_prefix, "")
+    }
+  end
+
+  defp prelude(opts) do
+    if Keyword.get(opts, :use_font_defaults, true), do: @font_defaults, else: ""
+  end
+
+  defp world_options(opts) do
+    %{
+      extra_fonts: Keyword.get(opts, :extra_fonts, []) ++ @embedded_fonts,
+      root: Keyword.get(opts, :root),
+      cache: Keyword.get(opts, :cache, :per_compile)
+    }
+  end
+
+  defp nif_options(opts) do
+    %{
+      encryption: encryption(Keyword.get(opts, :encrypt)),
+      max_pages: Keyword.get(opts, :max_pages),
+      assertions: Keyword.get(opts, :assertions, false),
+      reject_empty: Keyword.get(opts, :reject_empty, false),
+      expect_uniform_pages: Keyword.get(opts, :expect_uniform_pages, false),
+      debug: Keyword.get(opts, :debug, false),
+      requirements: requirements(Keyword.get(opts, :require, []))
+    }
+  end
+
+  defp requirements(requirements) do
+    %{
+      min_pages: Keyword.get(requirements, :min_pages, 0),
+      labels: Keyword.get(requirements, :labels, []),
+      text: Keyword.get(requirements, :text, [])
+    }
+  end
+
+  defp encryption(nil), do: nil
+
+  defp encryption(encrypt) do
+    %{
+      owner_password: Keyword.fetch!(encrypt, :owner_password),
+      user_password: Keyword.get(encrypt, :user_password, ""),
+      deny: Keyword.get(encrypt, :deny, [])
+    }
+  end
 end
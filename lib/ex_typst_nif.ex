@@ -3,5 +3,34 @@ defmodule ExTypst.NIF do
 
   use Rustler, otp_app: :ex_typst, crate: "extypst_nif"
 
-  def compile(_content, _font_paths), do: :erlang.nif_error(:nif_not_loaded)
+  def compile(_prelude, _files, _entry, _world_options, _options),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  def world_new(_options), do: :erlang.nif_error(:nif_not_loaded)
+
+  def world_compile(_world, _prelude, _files, _entry, _options),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  def compile_report(_prelude, _files, _entry, _world_options, _options, _report),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  def world_compile_report(_world, _prelude, _files, _entry, _options, _report),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  def world_trace(_world, _page, _x, _y), do: :erlang.nif_error(:nif_not_loaded)
+
+  def world_warnings(_world), do: :erlang.nif_error(:nif_not_loaded)
+
+  def world_log(_world), do: :erlang.nif_error(:nif_not_loaded)
+
+  def world_cache_stats(_world), do: :erlang.nif_error(:nif_not_loaded)
+
+  def batch_stream(_prelude, _contents, _world_options, _options, _sink),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  def batch_ack(_batch), do: :erlang.nif_error(:nif_not_loaded)
+
+  def supported_features, do: :erlang.nif_error(:nif_not_loaded)
+
+  def shutdown(_timeout), do: :erlang.nif_error(:nif_not_loaded)
 end
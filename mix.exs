@@ -39,7 +39,8 @@ defmodule ExTypst.MixProject do
       {:rustler, "~> 0.30.0"},
       {:benchee, "~> 1.0", only: :dev},
       {:credo, "~> 1.7", only: [:dev, :test], runtime: false},
-      {:ex_doc, "~> 0.27", only: :dev, runtime: false}
+      {:ex_doc, "~> 0.27", only: :dev, runtime: false},
+      {:jason, "~> 1.4"}
     ]
   end
 
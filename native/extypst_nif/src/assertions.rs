@@ -0,0 +1,124 @@
+//! Assertion functions templates can opt into, to encode invariants that
+//! fail the compilation when they don't hold.
+//!
+//! They are defined natively in the global scope, so that they are available
+//! in every file of a project, and fail with messages starting with `FAILED`
+//! to tell them apart from other errors, including typst's own assertions.
+
+use std::sync::LazyLock;
+
+use comemo::Tracked;
+use ecow::{eco_format, EcoString};
+use once_cell::sync::Lazy;
+use typst::diag::{bail, SourceResult};
+use typst::engine::Engine;
+use typst::foundations::{
+    Args, CastInfo, Context, Func, NativeFuncData, NativeFuncPtr, NoneValue, ParamInfo, Repr,
+    Scope, Str, Type, Value,
+};
+use typst::Library;
+use typst_utils::LazyHash;
+
+/// Prefix of the messages of failed assertions.
+pub const FAILED: &str = "template assertion failed: ";
+
+/// The standard library, along with the assertion functions.
+pub static LIBRARY: Lazy<LazyHash<Library>> = Lazy::new(|| {
+    let mut library = Library::builder().build();
+    let scope = library.global.scope_mut();
+    scope.define("assert-eq", Func::from(&ASSERT_EQ));
+    scope.define("assert-nonempty", Func::from(&ASSERT_NONEMPTY));
+    LazyHash::new(library)
+});
+
+static ASSERT_EQ: NativeFuncData = NativeFuncData {
+    function: NativeFuncPtr(&assert_eq),
+    name: "assert-eq",
+    title: "Assert Equal",
+    docs: "Fails the compilation unless two values are equal.",
+    keywords: &[],
+    contextual: false,
+    scope: LazyLock::new(Scope::new),
+    params: LazyLock::new(|| vec![param("left"), param("right"), message_param()]),
+    returns: LazyLock::new(|| CastInfo::Type(Type::of::<NoneValue>())),
+};
+
+static ASSERT_NONEMPTY: NativeFuncData = NativeFuncData {
+    function: NativeFuncPtr(&assert_nonempty),
+    name: "assert-nonempty",
+    title: "Assert Non-Empty",
+    docs: "Fails the compilation if a value is `none` or an empty string, array, \
+           dictionary or content.",
+    keywords: &[],
+    contextual: false,
+    scope: LazyLock::new(Scope::new),
+    params: LazyLock::new(|| vec![param("value"), message_param()]),
+    returns: LazyLock::new(|| CastInfo::Type(Type::of::<NoneValue>())),
+};
+
+fn assert_eq(_: &mut Engine, _: Tracked<Context>, args: &mut Args) -> SourceResult<Value> {
+    let left: Value = args.expect("left")?;
+    let right: Value = args.expect("right")?;
+    let message: Option<EcoString> = args.named("message")?;
+    let span = args.span;
+    args.take().finish()?;
+
+    if left != right {
+        let message = message.unwrap_or_else(|| {
+            eco_format!("expected {} to equal {}", left.repr(), right.repr())
+        });
+        bail!(span, "{}{}", FAILED, message);
+    }
+    Ok(Value::None)
+}
+
+fn assert_nonempty(_: &mut Engine, _: Tracked<Context>, args: &mut Args) -> SourceResult<Value> {
+    let value: Value = args.expect("value")?;
+    let message: Option<EcoString> = args.named("message")?;
+    let span = args.span;
+    args.take().finish()?;
+
+    let empty = match &value {
+        Value::None => true,
+        Value::Str(text) => text.is_empty(),
+        Value::Array(array) => array.is_empty(),
+        Value::Dict(dict) => dict.is_empty(),
+        Value::Content(content) => content.is_empty(),
+        _ => false,
+    };
+    if empty {
+        let message = message.unwrap_or_else(|| "expected a non-empty value".into());
+        bail!(span, "{}{}", FAILED, message);
+    }
+    Ok(Value::None)
+}
+
+/// A required positional parameter taking any value.
+fn param(name: &'static str) -> ParamInfo {
+    ParamInfo {
+        name,
+        docs: "",
+        input: CastInfo::Any,
+        default: None,
+        positional: true,
+        named: false,
+        variadic: false,
+        required: true,
+        settable: false,
+    }
+}
+
+/// The optional `message` parameter of the assertions.
+fn message_param() -> ParamInfo {
+    ParamInfo {
+        name: "message",
+        docs: "The message to fail with, instead of a generic one.",
+        input: CastInfo::Type(Type::of::<Str>()),
+        default: None,
+        positional: false,
+        named: true,
+        variadic: false,
+        required: false,
+        settable: false,
+    }
+}
@@ -0,0 +1,154 @@
+//! Streaming of batch compilations to a sink process.
+//!
+//! Every document of a batch is sent to the sink as soon as it is produced,
+//! but the next one is only compiled once the sink acknowledged the previous
+//! one. This keeps the NIF from racing ahead of slow consumers such as
+//! uploads.
+//!
+//! A batch counts as in-flight work until it finished, and stops between two
+//! documents when a shutdown cancels it.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use rustler::{Atom, Encoder, LocalPid, OwnedEnv, ResourceArc};
+
+use crate::jobs::{self, Job};
+use crate::{atoms, binary, catch_panic, compile_pdf, CompileOptions, Error, WorldOptions, MAIN};
+
+/// How often a batch waiting for an acknowledgment checks for cancellation.
+const CANCEL_POLL: Duration = Duration::from_millis(100);
+
+/// Source of unique batch ids, used to tag the messages of each batch.
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Handle to a running batch, used to acknowledge the documents it produces.
+///
+/// Dropping the handle (i.e. the BEAM garbage collecting it) stops the batch
+/// after the document that is currently waiting for an acknowledgment.
+pub struct BatchResource {
+    acks: SyncSender<()>,
+    waiting: Arc<AtomicBool>,
+}
+
+/// A batch being produced on its own thread.
+struct Batch {
+    id: u64,
+    prelude: String,
+    markups: Vec<String>,
+    world_options: WorldOptions,
+    options: CompileOptions,
+    sink: LocalPid,
+    acks: Receiver<()>,
+    /// Whether a document was sent and not acknowledged yet.
+    waiting: Arc<AtomicBool>,
+    _job: Job,
+}
+
+impl Batch {
+    fn run(self) {
+        let mut env = OwnedEnv::new();
+        // If the world can't even be built, every document fails with the
+        // same error.
+        let mut world = catch_panic(|| Ok(self.world_options.build()));
+
+        for (index, markup) in self.markups.iter().enumerate() {
+            if jobs::cancelled() {
+                self.finish(&mut env, atoms::cancelled());
+                return;
+            }
+
+            let result = catch_panic(|| {
+                let world = world.as_mut().map_err(|error| error.clone())?;
+                let files = vec![(MAIN.into(), markup.as_bytes().to_vec())];
+                let (_, pdf_bytes) =
+                    compile_pdf(world, &self.prelude, files, MAIN, &self.options)?;
+                Ok(binary(pdf_bytes))
+            });
+
+            // Set before sending, so that the sink can't acknowledge the
+            // document before it counts as waiting.
+            self.waiting.store(true, Ordering::SeqCst);
+            env.send_and_clear(&self.sink, |env| {
+                (atoms::ex_typst_batch(), self.id, index, result).encode(env)
+            });
+
+            match self.wait_for_ack() {
+                Ok(()) => {}
+                // The handle is gone and nobody is interested in the rest of
+                // the batch anymore.
+                Err(RecvTimeoutError::Disconnected) => return,
+                Err(RecvTimeoutError::Timeout) => {
+                    self.finish(&mut env, atoms::cancelled());
+                    return;
+                }
+            }
+        }
+
+        self.finish(&mut env, atoms::done());
+    }
+
+    /// Wait for the sink to acknowledge the last document, giving up with a
+    /// timeout once the batch is cancelled.
+    fn wait_for_ack(&self) -> Result<(), RecvTimeoutError> {
+        loop {
+            match self.acks.recv_timeout(CANCEL_POLL) {
+                Err(RecvTimeoutError::Timeout) if !jobs::cancelled() => continue,
+                result => return result,
+            }
+        }
+    }
+
+    /// Tell the sink that no more documents are coming.
+    fn finish(&self, env: &mut OwnedEnv, status: Atom) {
+        env.send_and_clear(&self.sink, |env| {
+            (atoms::ex_typst_batch(), self.id, status).encode(env)
+        });
+    }
+}
+
+#[rustler::nif]
+pub fn batch_stream(
+    prelude: String,
+    markups: Vec<String>,
+    world_options: WorldOptions,
+    options: CompileOptions,
+    sink: LocalPid,
+) -> Result<(ResourceArc<BatchResource>, u64), Error> {
+    let job = jobs::start()?;
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let (sender, receiver) = mpsc::sync_channel(1);
+    let waiting = Arc::new(AtomicBool::new(false));
+    let batch = Batch {
+        id,
+        prelude,
+        markups,
+        world_options,
+        options,
+        sink,
+        acks: receiver,
+        waiting: waiting.clone(),
+        _job: job,
+    };
+
+    thread::spawn(move || batch.run());
+    let resource = BatchResource {
+        acks: sender,
+        waiting,
+    };
+    Ok((ResourceArc::new(resource), id))
+}
+
+#[rustler::nif]
+pub fn batch_ack(resource: ResourceArc<BatchResource>) -> Atom {
+    // Only the document waiting for it is released, so acknowledgments sent
+    // twice or ahead of a document don't let the batch run ahead. As there is
+    // at most one acknowledgment in flight then, sending never blocks.
+    if resource.waiting.swap(false, Ordering::SeqCst) {
+        let _ = resource.acks.try_send(());
+    }
+    atoms::ok()
+}
@@ -0,0 +1,156 @@
+//! Caching of the files a world reads from disk.
+//!
+//! Every world has its own cache, configured by a policy, so that worlds
+//! serving high-churn templates can opt out of caching without affecting
+//! worlds whose files rarely change.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use rustler::{Atom, Decoder, Encoder, Env, NifMap, NifResult, Term};
+use typst::diag::FileResult;
+use typst::syntax::FileId;
+
+use crate::{atoms, FileSlot};
+
+/// How long files read from disk are kept around.
+#[derive(Debug, Clone, Copy)]
+pub enum CachePolicy {
+    /// Files are read again every time they are accessed.
+    None,
+    /// Files are kept for the duration of a single compilation.
+    PerCompile,
+    /// Files are kept across compilations until they are older than the TTL.
+    Persistent(Duration),
+}
+
+impl<'a> Decoder<'a> for CachePolicy {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        if let Ok((tag, ttl)) = term.decode::<(Atom, u64)>() {
+            if tag == atoms::persistent() {
+                return Ok(Self::Persistent(Duration::from_millis(ttl)));
+            }
+        } else if let Ok(atom) = term.decode::<Atom>() {
+            if atom == atoms::none() {
+                return Ok(Self::None);
+            } else if atom == atoms::per_compile() {
+                return Ok(Self::PerCompile);
+            }
+        }
+        Err(rustler::Error::BadArg)
+    }
+}
+
+impl Encoder for CachePolicy {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        match self {
+            Self::None => atoms::none().encode(env),
+            Self::PerCompile => atoms::per_compile().encode(env),
+            Self::Persistent(ttl) => (atoms::persistent(), ttl.as_millis() as u64).encode(env),
+        }
+    }
+}
+
+/// Counters of a world's file cache.
+#[derive(NifMap, Debug, Clone, Copy)]
+pub struct CacheStats {
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+    entries: usize,
+}
+
+struct Entry {
+    read_at: Instant,
+    file: FileResult<Arc<FileSlot>>,
+}
+
+/// A cache of files read from disk, keyed by their id.
+pub struct FileCache {
+    policy: CachePolicy,
+    entries: RwLock<HashMap<FileId, Entry>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl FileCache {
+    pub fn new(policy: CachePolicy) -> Self {
+        Self {
+            policy,
+            entries: RwLock::default(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// Get a file from the cache, or `read` it if it isn't cached or expired.
+    pub fn get_or_read(
+        &self,
+        id: FileId,
+        read: impl FnOnce() -> FileResult<Arc<FileSlot>>,
+    ) -> FileResult<Arc<FileSlot>> {
+        if let Some(entry) = self.entries.read().unwrap().get(&id) {
+            if !self.expired(entry) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return entry.file.clone();
+            }
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let file = read();
+        if !matches!(self.policy, CachePolicy::None) {
+            let entry = Entry {
+                read_at: Instant::now(),
+                file: file.clone(),
+            };
+            if self.entries.write().unwrap().insert(id, entry).is_some() {
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        file
+    }
+
+    /// Prepare the cache for the next compilation.
+    pub fn reset(&mut self) {
+        let entries = self.entries.get_mut().unwrap();
+        let before = entries.len();
+        match self.policy {
+            CachePolicy::None => {}
+            CachePolicy::PerCompile => entries.clear(),
+            CachePolicy::Persistent(ttl) => {
+                entries.retain(|_, entry| entry.read_at.elapsed() < ttl)
+            }
+        }
+        self.evictions.fetch_add((before - entries.len()) as u64, Ordering::Relaxed);
+    }
+
+    /// Find a cached file with the given contents.
+    pub fn find(&self, data: &[u8]) -> Option<Arc<FileSlot>> {
+        let entries = self.entries.read().unwrap();
+        entries
+            .values()
+            .filter_map(|entry| entry.file.as_ref().ok())
+            .find(|slot| slot.bytes.as_slice() == data)
+            .cloned()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            entries: self.entries.read().unwrap().len(),
+        }
+    }
+
+    fn expired(&self, entry: &Entry) -> bool {
+        match self.policy {
+            CachePolicy::Persistent(ttl) => entry.read_at.elapsed() >= ttl,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! Logging of how a world resolves files and fonts, to diagnose templates
+//! that behave differently from one machine to another.
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use rustler::{Atom, NifMap};
+
+/// A resolution decision of a world.
+#[derive(NifMap, Clone)]
+pub struct Event {
+    /// What was resolved: `:file` or `:font`.
+    kind: Atom,
+    /// The path of the file as requested, or of the font file.
+    path: String,
+    /// How it was resolved: `:memory` (found among the files passed in),
+    /// `:read` (read from disk), `:cache_hit`, `:loaded` (a font was loaded)
+    /// or `:failed`.
+    outcome: Atom,
+    /// The path a file was read from, the error it failed with, or the
+    /// family of a font.
+    detail: Option<String>,
+}
+
+impl Event {
+    pub fn new(kind: Atom, path: String, outcome: Atom, detail: Option<String>) -> Self {
+        Self {
+            kind,
+            path,
+            outcome,
+            detail,
+        }
+    }
+}
+
+/// The events of a compilation, if it is being logged.
+#[derive(Default)]
+pub struct Log(Mutex<Option<Vec<Event>>>);
+
+impl Log {
+    /// Start over for a new compilation, logging it or not.
+    pub fn start(&self, enabled: bool) {
+        *self.lock() = enabled.then(Vec::new);
+    }
+
+    /// Record an event, if the compilation is being logged.
+    pub fn record(&self, event: impl FnOnce() -> Event) {
+        if let Some(events) = self.lock().as_mut() {
+            events.push(event());
+        }
+    }
+
+    /// The events logged so far, in the order they happened.
+    pub fn events(&self) -> Vec<Event> {
+        self.lock().clone().unwrap_or_default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<Vec<Event>>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
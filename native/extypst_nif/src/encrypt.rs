@@ -0,0 +1,77 @@
+//! Password protection of exported PDFs.
+//!
+//! typst-pdf has no support for encryption, so the exported buffer is loaded
+//! again and encrypted with AES-128 (the standard security handler's revision
+//! 4), which every PDF reader in use today understands.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::sync::Arc;
+
+use ecow::EcoString;
+use lopdf::encryption::crypt_filters::{Aes128CryptFilter, CryptFilter};
+use lopdf::encryption::{EncryptionState, EncryptionVersion, Permissions};
+use lopdf::Document;
+use rustler::{NifMap, NifUnitEnum};
+use typst::diag::StrResult;
+
+/// Passwords and permissions of an encrypted PDF.
+#[derive(NifMap)]
+pub struct Encryption {
+    user_password: String,
+    owner_password: String,
+    deny: Vec<Permission>,
+}
+
+/// An action that can be denied to readers who only know the user password.
+#[derive(NifUnitEnum, Clone, Copy)]
+pub enum Permission {
+    Print,
+    Copy,
+    Modify,
+    Annotate,
+}
+
+impl Permission {
+    fn flags(self) -> Permissions {
+        match self {
+            Self::Print => Permissions::PRINTABLE | Permissions::PRINTABLE_IN_HIGH_QUALITY,
+            // Extraction for accessibility stays allowed, so that screen
+            // readers keep working on documents that can't be copied from.
+            Self::Copy => Permissions::COPYABLE,
+            Self::Modify => Permissions::MODIFIABLE | Permissions::ASSEMBLABLE,
+            Self::Annotate => Permissions::ANNOTABLE | Permissions::FILLABLE,
+        }
+    }
+}
+
+/// Encrypt an exported PDF.
+pub fn encrypt(pdf: &[u8], encryption: &Encryption) -> StrResult<Vec<u8>> {
+    let mut document = Document::load_mem(pdf).map_err(failed)?;
+    let permissions = encryption
+        .deny
+        .iter()
+        .fold(Permissions::all(), |permissions, denied| permissions - denied.flags());
+
+    let filter: Arc<dyn CryptFilter> = Arc::new(Aes128CryptFilter);
+    let version = EncryptionVersion::V4 {
+        document: &document,
+        encrypt_metadata: true,
+        crypt_filters: BTreeMap::from([(b"StdCF".to_vec(), filter)]),
+        stream_filter: b"StdCF".to_vec(),
+        string_filter: b"StdCF".to_vec(),
+        owner_password: &encryption.owner_password,
+        user_password: &encryption.user_password,
+        permissions,
+    };
+    let state = EncryptionState::try_from(version).map_err(failed)?;
+    document.encrypt(&state).map_err(failed)?;
+
+    let mut buffer = Vec::new();
+    document.save_to(&mut buffer).map_err(failed)?;
+    Ok(buffer)
+}
+
+fn failed(error: impl Display) -> EcoString {
+    format!("PDF encryption failed: {error}").into()
+}
@@ -0,0 +1,39 @@
+//! What a build of the NIF supports, as selected by cargo features.
+
+use rustler::{Atom, NifMap};
+
+use crate::atoms;
+
+#[cfg(not(feature = "typst-0-13"))]
+compile_error!("a typst release has to be selected with a feature, e.g. `typst-0-13`");
+
+/// The typst release the NIF is built against.
+#[cfg(feature = "typst-0-13")]
+const TYPST_VERSION: &str = "0.13.1";
+
+/// The features of this build.
+#[derive(NifMap)]
+pub struct Features {
+    typst_version: String,
+    svg_export: bool,
+    png_export: bool,
+    html_export: bool,
+    pdf_standards: Vec<Atom>,
+    plugins: bool,
+    encryption: bool,
+}
+
+#[rustler::nif]
+pub fn supported_features() -> Features {
+    Features {
+        typst_version: TYPST_VERSION.into(),
+        svg_export: cfg!(feature = "svg"),
+        png_export: cfg!(feature = "png"),
+        // typst's HTML export is still experimental and not exposed, and PDFs
+        // are always exported as plain PDF 1.7.
+        html_export: false,
+        pdf_standards: vec![atoms::pdf_1_7()],
+        plugins: true,
+        encryption: true,
+    }
+}
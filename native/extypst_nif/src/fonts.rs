@@ -0,0 +1,69 @@
+//! Report of the font faces used by compiled documents.
+
+use std::collections::HashSet;
+
+use rustler::{Atom, NifMap};
+use typst::layout::{FrameItem, PagedDocument, Transform};
+use typst::text::{Font, FontStyle};
+
+use crate::inspect::walk;
+use crate::{atoms, SystemWorld};
+
+/// A font face used in a document, and how much of it.
+#[derive(NifMap)]
+pub struct FontUsage {
+    family: String,
+    style: Atom,
+    weight: u16,
+    stretch: f64,
+    /// Path of the font file, and index of the face in it.
+    path: Option<String>,
+    index: u32,
+    /// Number of glyphs set in the face, and how many of them are distinct.
+    glyphs: usize,
+    distinct_glyphs: usize,
+}
+
+/// List the font faces used in a document compiled in `world`, in the order
+/// they first appear.
+pub fn report(world: &SystemWorld, document: &PagedDocument) -> Vec<FontUsage> {
+    let mut faces: Vec<(Font, usize, HashSet<u16>)> = vec![];
+    for page in &document.pages {
+        walk(&page.frame, Transform::identity(), &mut |_, item| {
+            let FrameItem::Text(text) = item else {
+                return;
+            };
+            let i = match faces.iter().position(|(font, ..)| *font == text.font) {
+                Some(i) => i,
+                None => {
+                    faces.push((text.font.clone(), 0, HashSet::new()));
+                    faces.len() - 1
+                }
+            };
+            let (_, glyphs, distinct) = &mut faces[i];
+            *glyphs += text.glyphs.len();
+            distinct.extend(text.glyphs.iter().map(|glyph| glyph.id));
+        });
+    }
+
+    faces
+        .into_iter()
+        .map(|(font, glyphs, distinct)| {
+            let info = font.info();
+            FontUsage {
+                family: info.family.clone(),
+                style: match info.variant.style {
+                    FontStyle::Normal => atoms::normal(),
+                    FontStyle::Italic => atoms::italic(),
+                    FontStyle::Oblique => atoms::oblique(),
+                },
+                weight: info.variant.weight.to_number(),
+                stretch: info.variant.stretch.to_ratio().get(),
+                path: world.font_path(&font),
+                index: font.index(),
+                glyphs,
+                distinct_glyphs: distinct.len(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,71 @@
+//! Listing of the images placed in compiled documents.
+
+use rustler::NifMap;
+use sha2::{Digest, Sha256};
+use typst::foundations::Bytes;
+use typst::layout::{FrameItem, PagedDocument, Transform};
+use typst::visualize::{Image, ImageKind};
+
+use crate::inspect::walk;
+use crate::{SourcePosition, SystemWorld};
+
+/// An image placed in a document. Coordinates and sizes are in points, with
+/// the position being the top left corner of the image, measured from the
+/// top left corner of the page.
+#[derive(NifMap)]
+pub struct PlacedImage {
+    page: usize,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    /// Size of the image itself: pixels for raster images, points for SVGs.
+    natural_width: f64,
+    natural_height: f64,
+    /// Hex-encoded SHA-256 hash of the image's data.
+    sha256: String,
+    /// Path of the file the image was loaded from, if known.
+    path: Option<String>,
+    /// Position of the element that placed the image.
+    position: Option<SourcePosition>,
+}
+
+/// List the images of a document compiled in `world`, in the order they
+/// appear on the pages.
+pub fn extract(world: &SystemWorld, document: &PagedDocument) -> Vec<PlacedImage> {
+    let mut images = vec![];
+    for (i, page) in document.pages.iter().enumerate() {
+        walk(&page.frame, Transform::identity(), &mut |ts, item| {
+            let FrameItem::Image(image, size, span) = item else {
+                return;
+            };
+            let data = data(image);
+            images.push(PlacedImage {
+                page: i + 1,
+                x: ts.tx.to_pt(),
+                y: ts.ty.to_pt(),
+                width: size.x.to_pt(),
+                height: size.y.to_pt(),
+                natural_width: image.width(),
+                natural_height: image.height(),
+                sha256: sha256(data.as_slice()),
+                path: world.path_of(data.as_slice()),
+                position: world.span_position(*span),
+            });
+        });
+    }
+    images
+}
+
+/// The hex-encoded SHA-256 hash of some data.
+pub(crate) fn sha256(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// The encoded data of an image, as it was loaded.
+fn data(image: &Image) -> &Bytes {
+    match image.kind() {
+        ImageKind::Raster(raster) => raster.data(),
+        ImageKind::Svg(svg) => svg.data(),
+    }
+}
@@ -0,0 +1,52 @@
+//! Inspection of the layout of compiled documents.
+
+use typst::layout::{Frame, FrameItem, PagedDocument, Transform};
+
+/// Whether a frame contains anything visible, as opposed to only
+/// introspection tags, links or empty groups.
+pub fn has_content(frame: &Frame) -> bool {
+    frame.items().any(|(_, item)| match item {
+        FrameItem::Group(group) => has_content(&group.frame),
+        FrameItem::Text(_) | FrameItem::Shape(..) | FrameItem::Image(..) => true,
+        FrameItem::Link(..) | FrameItem::Tag(_) => false,
+    })
+}
+
+/// Call `f` for every item of `frame` and of its groups, along with the
+/// transform from the item's coordinate system to the frame's.
+pub fn walk<'a>(
+    frame: &'a Frame,
+    ts: Transform,
+    f: &mut impl FnMut(Transform, &'a FrameItem),
+) {
+    for (pos, item) in frame.items() {
+        let ts = ts.pre_concat(Transform::translate(pos.x, pos.y));
+        f(ts, item);
+        if let FrameItem::Group(group) = item {
+            walk(&group.frame, ts.pre_concat(group.transform), f);
+        }
+    }
+}
+
+/// The text of a frame, with line breaks between text on different
+/// baselines.
+pub fn text(frame: &Frame) -> String {
+    let mut text = String::new();
+    let mut baseline = None;
+    walk(frame, Transform::identity(), &mut |ts, item| {
+        if let FrameItem::Text(run) = item {
+            if baseline.is_some_and(|y| y != ts.ty) {
+                text.push('\n');
+            }
+            baseline = Some(ts.ty);
+            text.push_str(&run.text);
+        }
+    });
+    text
+}
+
+/// The text of all pages of a document, separated by line breaks.
+pub fn document_text(document: &PagedDocument) -> String {
+    let pages: Vec<_> = document.pages.iter().map(|page| text(&page.frame)).collect();
+    pages.join("\n")
+}
@@ -0,0 +1,67 @@
+//! Bookkeeping of in-flight work, so that the NIF can be shut down without
+//! cutting compilations short.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use crate::Error;
+
+struct Jobs {
+    accepting: bool,
+    in_flight: usize,
+}
+
+static JOBS: Mutex<Jobs> = Mutex::new(Jobs {
+    accepting: true,
+    in_flight: 0,
+});
+static DRAINED: Condvar = Condvar::new();
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// A unit of in-flight work, finished when dropped.
+pub struct Job {
+    _private: (),
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        let mut jobs = JOBS.lock().unwrap();
+        jobs.in_flight -= 1;
+        if jobs.in_flight == 0 {
+            DRAINED.notify_all();
+        }
+    }
+}
+
+/// Register a new unit of work, unless the NIF is shutting down.
+pub fn start() -> Result<Job, Error> {
+    let mut jobs = JOBS.lock().unwrap();
+    if !jobs.accepting {
+        return Err(Error::Shutdown);
+    }
+    jobs.in_flight += 1;
+    Ok(Job { _private: () })
+}
+
+/// Whether long-running jobs should give up at their next opportunity.
+pub fn cancelled() -> bool {
+    CANCELLED.load(Ordering::Relaxed)
+}
+
+/// Stop accepting new work and wait up to `timeout` for the work in flight
+/// to finish, cancelling whatever is left after that.
+///
+/// Returns the number of jobs that were still running at the deadline.
+pub fn shutdown(timeout: Duration) -> usize {
+    let mut jobs = JOBS.lock().unwrap();
+    jobs.accepting = false;
+
+    let (jobs, _) = DRAINED
+        .wait_timeout_while(jobs, timeout, |jobs| jobs.in_flight > 0)
+        .unwrap();
+    if jobs.in_flight > 0 {
+        CANCELLED.store(true, Ordering::Relaxed);
+    }
+    jobs.in_flight
+}
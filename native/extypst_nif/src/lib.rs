@@ -1,32 +1,196 @@
 use std::collections::HashMap;
 use std::fs::{self, File};
-use std::hash::Hash;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
-use std::sync::{RwLock, Arc};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError, Weak};
+use std::time::Duration;
 
 use typst_utils::LazyHash;
-use elsa::sync::FrozenVec;
+use ecow::{eco_format, EcoString, EcoVec};
 use memmap2::Mmap;
 use once_cell::sync::OnceCell;
-use same_file::Handle;
-use siphasher::sip128::{Hasher128, SipHasher13};
-use typst::diag::{FileError, FileResult, StrResult};
+use rustler::{Atom, Binary, Encoder, Env, NifMap, ResourceArc, Term};
+use typst::diag::{FileError, FileResult, SourceDiagnostic, StrResult};
 use typst::foundations::{Bytes, Datetime};
-use typst::syntax::{FileId, Source, VirtualPath};
+use typst::layout::{Abs, PagedDocument, Point};
+use typst::syntax::{FileId, Source, Span, VirtualPath};
 use typst::text::{Font, FontBook, FontInfo};
-use typst::{Library, World};
+use typst::{Library, World, WorldExt};
+use typst_ide::{IdeWorld, Jump};
 use walkdir::WalkDir;
 
+use crate::cache::{CachePolicy, CacheStats, FileCache};
+use crate::inspect::has_content;
+use crate::report::Report;
+
+mod assertions;
+mod batch;
+mod cache;
+mod debug;
+mod encrypt;
+mod features;
+mod fonts;
+mod images;
+mod inspect;
+mod jobs;
+mod overflow;
+mod pages;
+mod preview;
+mod report;
+mod svg;
+mod tables;
+mod validate;
+mod values;
+
+mod atoms {
+    rustler::atoms! {
+        ok,
+        error,
+        done,
+        cancelled,
+        ex_typst_batch,
+        no_document,
+        invalid_page,
+        not_found,
+        shutdown,
+        timeout,
+        internal,
+        empty_document,
+        page_limit,
+        assertion_failed,
+        mixed_page_sizes,
+        portrait,
+        landscape,
+        validation,
+        min_pages,
+        missing_label,
+        missing_text,
+        compiler,
+        page_overflow,
+        container_overflow,
+        normal,
+        italic,
+        oblique,
+        tables,
+        images,
+        fonts,
+        svg,
+        preview,
+        pages,
+        metadata,
+        auto,
+        decimal,
+        __struct__,
+        date = "Elixir.Date",
+        time = "Elixir.Time",
+        naive_datetime = "Elixir.NaiveDateTime",
+        calendar,
+        calendar_iso = "Elixir.Calendar.ISO",
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        microsecond,
+        infinity,
+        neg_infinity,
+        nan,
+        file,
+        font,
+        memory,
+        read,
+        cache_hit,
+        loaded,
+        failed,
+        pdf_1_7,
+        feature_disabled,
+        svg_export,
+        png_export,
+        none,
+        per_compile,
+        persistent,
+    }
+}
+
+/// An error passed back to elixir.
+#[derive(Clone)]
+pub enum Error {
+    /// Compilation or export failed, with a human-readable explanation.
+    Message(EcoString),
+    /// The NIF was shut down and doesn't accept new work.
+    Shutdown,
+    /// typst (or the NIF itself) panicked.
+    Internal(String),
+    /// The document has no content, but the options asked to reject that.
+    EmptyDocument,
+    /// The document has more pages than the options allow.
+    PageLimit(usize),
+    /// Assertions of the template failed.
+    Assertion(Vec<AssertionFailure>),
+    /// The pages of the document differ in size, but the options asked for
+    /// them to be uniform.
+    MixedPageSizes(Vec<pages::PageSize>),
+    /// The document failed to meet the requirements of the options.
+    Validation(Vec<validate::Failure>),
+    /// The request needs a feature that was compiled out of the NIF.
+    FeatureDisabled(Atom),
+}
+
+impl From<EcoString> for Error {
+    fn from(message: EcoString) -> Self {
+        Self::Message(message)
+    }
+}
+
+impl Encoder for Error {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        match self {
+            Self::Message(message) => message.as_str().encode(env),
+            Self::Shutdown => atoms::shutdown().encode(env),
+            Self::Internal(message) => (atoms::internal(), message).encode(env),
+            Self::EmptyDocument => atoms::empty_document().encode(env),
+            Self::PageLimit(limit) => (atoms::page_limit(), limit).encode(env),
+            Self::Assertion(failures) => (atoms::assertion_failed(), failures).encode(env),
+            Self::MixedPageSizes(sizes) => (atoms::mixed_page_sizes(), sizes).encode(env),
+            Self::Validation(failures) => (atoms::validation(), failures).encode(env),
+            Self::FeatureDisabled(feature) => (atoms::feature_disabled(), feature).encode(env),
+        }
+    }
+}
+
+/// Run `f`, turning a panic into an internal error instead of letting it
+/// unwind into the VM.
+pub(crate) fn catch_panic<T>(f: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".into());
+        Err(Error::Internal(message))
+    })
+}
+
 /// A world that provides access to the operating system.
 pub struct SystemWorld {
-    root: PathBuf,
+    /// Directory that files not found in memory are read from, if any.
+    root: Option<PathBuf>,
     library: LazyHash<Library>,
     book: LazyHash<FontBook>,
     fonts: Vec<FontSlot>,
-    hashes: RwLock<HashMap<PathBuf, FileResult<PathHash>>>,
-    paths: RwLock<HashMap<PathHash, PathSlot>>,
-    sources: FrozenVec<Box<Source>>,
+    files: HashMap<VirtualPath, Arc<FileSlot>>,
+    disk: FileCache,
     main_id: FileId,
+    /// Length in bytes of the prelude prepended to the main source.
+    prelude_len: usize,
+    /// Whether the assertion functions are available to the template.
+    assertions: bool,
+    /// Warnings emitted by the compiler during the last compilation.
+    warnings: EcoVec<SourceDiagnostic>,
+    /// How files and fonts were resolved during the last compilation, if it
+    /// was logged.
+    log: debug::Log,
 }
 
 /// Holds details about the location of a font and lazily the font itself.
@@ -37,16 +201,39 @@ struct FontSlot {
     font: OnceCell<Option<Font>>,
 }
 
-/// Holds canonical data for all paths pointing to the same entity.
-#[derive(Default)]
-struct PathSlot {
-    source_id: OnceCell<FileResult<FileId>>,
-    buffer: OnceCell<FileResult<Bytes>>,
+/// Holds a file of the world and lazily its parsed source.
+pub(crate) struct FileSlot {
+    id: FileId,
+    bytes: Bytes,
+    source: OnceCell<FileResult<Source>>,
+}
+
+impl FileSlot {
+    fn new(id: FileId, bytes: Bytes) -> Self {
+        Self {
+            id,
+            bytes,
+            source: OnceCell::new(),
+        }
+    }
+
+    fn source(&self) -> FileResult<Source> {
+        self.source
+            .get_or_init(|| {
+                let text =
+                    std::str::from_utf8(&self.bytes).map_err(|_| FileError::InvalidUtf8)?;
+                Ok(Source::new(self.id, text.into()))
+            })
+            .clone()
+    }
 }
 
 impl World for SystemWorld {
     fn library(&self) -> &LazyHash<Library> {
-        &self.library
+        match self.assertions {
+            true => &assertions::LIBRARY,
+            false => &self.library,
+        }
     }
 
     fn book(&self) -> &LazyHash<FontBook> {
@@ -58,32 +245,40 @@ impl World for SystemWorld {
     }
 
     fn source(&self, id: FileId) -> FileResult<Source> {
-        // Find the source by id
-        for (_index, source) in self.sources.iter().enumerate() {
-            // Check if this is the source we're looking for
-            if source.id() == id {
-                return Ok(source.clone());
-            }
-        }
-        Err(FileError::NotFound(PathBuf::from("source not found")))
+        self.lookup(id)?.source()
     }
 
     fn file(&self, id: FileId) -> FileResult<Bytes> {
-        // Get the source to find its content
-        let source = self.source(id)?;
-        // For virtual sources (like our main document), return the source text as bytes
-        Ok(Bytes::new(source.text().as_bytes().to_vec()))
+        Ok(self.lookup(id)?.bytes.clone())
     }
 
     fn font(&self, index: usize) -> Option<Font> {
         let slot = self.fonts.get(index)?;
 
-        slot.font
+        let mut loaded = false;
+        let font = slot
+            .font
             .get_or_init(|| {
-                let data = read(&slot.path).ok()?;
-                Font::new(Bytes::new(data), slot.index)
+                loaded = true;
+                let data = Bytes::new(read(&slot.path).ok()?);
+                // A malformed font file is treated as missing instead of
+                // taking the whole compilation down.
+                panic::catch_unwind(AssertUnwindSafe(|| Font::new(data, slot.index)))
+                    .ok()
+                    .flatten()
             })
-            .clone()
+            .clone();
+
+        self.log.record(|| {
+            let outcome = match (&font, loaded) {
+                (None, _) => atoms::failed(),
+                (Some(_), true) => atoms::loaded(),
+                (Some(_), false) => atoms::cache_hit(),
+            };
+            let family = font.as_ref().map(|font| font.info().family.clone());
+            debug::Event::new(atoms::font(), slot.path.display().to_string(), outcome, family)
+        });
+        font
     }
 
     fn today(&self, _offset: Option<i64>) -> Option<Datetime> {
@@ -92,8 +287,19 @@ impl World for SystemWorld {
     }
 }
 
+impl IdeWorld for SystemWorld {
+    fn upcast(&self) -> &dyn World {
+        self
+    }
+}
+
 impl SystemWorld {
-    pub fn new(root: PathBuf, font_paths: &[PathBuf], font_files: &[PathBuf]) -> Self {
+    pub fn new(
+        root: Option<PathBuf>,
+        cache: CachePolicy,
+        font_paths: &[PathBuf],
+        font_files: &[PathBuf],
+    ) -> Self {
         let mut searcher = FontSearcher::new();
         searcher.search_system();
 
@@ -109,89 +315,378 @@ impl SystemWorld {
             library: LazyHash::new(Library::builder().build()),
             book: LazyHash::new(searcher.book),
             fonts: searcher.fonts,
-            hashes: RwLock::default(),
-            paths: RwLock::default(),
-            sources: FrozenVec::new(),
-            main_id: FileId::new(None, VirtualPath::new("MARKUP.typ")),
+            files: HashMap::new(),
+            disk: FileCache::new(cache),
+            main_id: FileId::new(None, VirtualPath::new(MAIN)),
+            prelude_len: 0,
+            assertions: false,
+            warnings: EcoVec::new(),
+            log: debug::Log::default(),
         }
     }
 
-    fn slot(&self, path: &Path) -> FileResult<Arc<PathSlot>> {
-        let mut hashes = self.hashes.write().unwrap();
-        let hash = match hashes.get(path).cloned() {
-            Some(hash) => hash,
+    /// Find a file of the world, looking in memory first and on disk second.
+    fn lookup(&self, id: FileId) -> FileResult<Arc<FileSlot>> {
+        let path = id.vpath();
+        let not_found = || FileError::NotFound(path.as_rootless_path().into());
+        // Details are only put together when the compilation is logged.
+        let event = |outcome, detail: Option<String>| {
+            let name = path.as_rootless_path().display().to_string();
+            debug::Event::new(atoms::file(), name, outcome, detail)
+        };
+
+        if let Some(package) = id.package() {
+            self.log.record(|| {
+                event(atoms::failed(), Some(format!("packages are not supported: {package}")))
+            });
+            return Err(not_found());
+        }
+        if let Some(slot) = self.files.get(path) {
+            self.log.record(|| event(atoms::memory(), None));
+            return Ok(slot.clone());
+        }
+
+        let Some(root) = &self.root else {
+            self.log.record(|| {
+                event(atoms::failed(), Some("no root directory to read files from".into()))
+            });
+            return Err(not_found());
+        };
+
+        let mut read_now = false;
+        let file = self.disk.get_or_read(id, || {
+            read_now = true;
+            let path = path.resolve(root).ok_or(FileError::AccessDenied)?;
+            let data = read(&path)?;
+            Ok(Arc::new(FileSlot::new(id, Bytes::new(data))))
+        });
+
+        let resolved = || match path.resolve(root) {
+            Some(resolved) => resolved.display().to_string(),
             None => {
-                let hash = PathHash::new(path);
-                if let Ok(canon) = path.canonicalize() {
-                    hashes.insert(canon, hash.clone());
-                }
-                hashes.insert(path.into(), hash.clone());
-                hash
+                let path = path.as_rootless_path().display();
+                format!("{path} is outside of {}", root.display())
             }
-        }?;
-        drop(hashes);
-
-        let mut paths = self.paths.write().unwrap();
-        let _slot = paths.entry(hash).or_default();
-        // Clone the slot into an Arc for shared ownership
-        Ok(Arc::new(PathSlot {
-            source_id: OnceCell::new(),
-            buffer: OnceCell::new(),
-        }))
-    }
-
-    fn insert(&self, path: &Path, text: String) -> FileId {
-        let id = FileId::new(None, VirtualPath::new(path));
-        let source = Source::new(id, text);
-        self.sources.push(Box::new(source));
+        };
+        self.log.record(|| match &file {
+            Ok(_) if read_now => event(atoms::read(), Some(resolved())),
+            Ok(_) => event(atoms::cache_hit(), Some(resolved())),
+            Err(error) => event(atoms::failed(), Some(format!("{}: {error}", resolved()))),
+        });
+        file
+    }
+
+    /// Path of the file with the given contents, among the files in memory
+    /// and those cached from disk.
+    fn path_of(&self, data: &[u8]) -> Option<String> {
+        let slot = self
+            .files
+            .values()
+            .find(|slot| slot.bytes.as_slice() == data)
+            .cloned()
+            .or_else(|| self.disk.find(data))?;
+        Some(slot.id.vpath().as_rootless_path().to_string_lossy().into_owned())
+    }
+
+    /// Path of the file a loaded font was read from.
+    fn font_path(&self, font: &Font) -> Option<String> {
+        let slot = self
+            .fonts
+            .iter()
+            .find(|slot| slot.font.get().is_some_and(|loaded| loaded.as_ref() == Some(font)))?;
+        Some(slot.path.to_string_lossy().into_owned())
+    }
+
+    /// Statistics of the cache of files read from disk.
+    fn cache_stats(&self) -> CacheStats {
+        self.disk.stats()
+    }
+
+    /// Register an in-memory file under the given path, replacing any file
+    /// previously registered there.
+    fn insert(&mut self, path: VirtualPath, bytes: Bytes) -> FileId {
+        let id = FileId::new(None, path.clone());
+        self.files.insert(path, Arc::new(FileSlot::new(id, bytes)));
         id
     }
 
     fn reset(&mut self) {
-        // Clear caches - note: FrozenVec doesn't support clearing, so we'll create a new one
-        *self = Self::new(
-            self.root.clone(),
-            &[], // No additional font paths for reset
-            &[],
-        );
+        // Drop the files of the previous compilation, but keep the fonts
+        // around so that persistent worlds don't have to load them again.
+        self.files.clear();
+        self.disk.reset();
     }
 
-    pub fn compile(&mut self, markup: String) -> StrResult<Vec<u8>> {
+    /// Compile a project of in-memory files, starting from `entry`. The
+    /// files can import, include and read each other by their paths.
+    pub fn compile_project(
+        &mut self,
+        prelude: &str,
+        files: Vec<(String, Vec<u8>)>,
+        entry: &str,
+        options: &CompileOptions,
+    ) -> Result<PagedDocument, Error> {
         self.reset();
-        self.main_id = self.insert(Path::new("MARKUP.typ"), markup);
-
-        let result = typst::compile(self);
-        match result.output {
-            Ok(document) => {
-                let buffer = typst_pdf::pdf(&document, &typst_pdf::PdfOptions::default())
-                    .map_err(|e| format!("PDF export failed: {:?}", e))?;
-                Ok(buffer)
+        for (path, data) in files {
+            self.insert(VirtualPath::new(path), Bytes::new(data));
+        }
+
+        let entry = VirtualPath::new(entry);
+        let Some(slot) = self.files.get(&entry) else {
+            let path = entry.as_rootless_path().display();
+            return Err(Error::Message(eco_format!("entry file not found: {path}")));
+        };
+        let markup = slot
+            .source()
+            .map_err(|e| Error::Message(eco_format!("invalid entry file: {e}")))?;
+
+        self.prelude_len = prelude.len();
+        self.assertions = options.assertions;
+        let main = format!("{prelude}{}", markup.text());
+        self.main_id = self.insert(entry, Bytes::new(main.into_bytes()));
+
+        let result = typst::compile::<PagedDocument>(self);
+        self.warnings = result.warnings;
+        result.output.map_err(|errors| self.compile_error(&errors, options))
+    }
+
+    /// Turn the errors of a compilation into an error for elixir: the failed
+    /// assertions of the template if they are enabled and that's all there
+    /// is, or otherwise a message listing all errors.
+    fn compile_error(&self, errors: &[SourceDiagnostic], options: &CompileOptions) -> Error {
+        if options.assertions
+            && errors.iter().all(|error| error.message.starts_with(assertions::FAILED))
+        {
+            let failures = errors.iter().map(|error| self.assertion_failure(error));
+            return Error::Assertion(failures.collect());
+        }
+
+        let mut error_msg = "compile error:\n".to_string();
+        for error in errors.iter() {
+            error_msg.push_str(&format!("{}\n", error.message));
+        }
+        Error::Message(error_msg.into())
+    }
+
+    /// Describe a failed assertion. Its position is that of the assertion's
+    /// call, or else of the innermost call leading to it that can be mapped
+    /// to a source.
+    fn assertion_failure(&self, error: &SourceDiagnostic) -> AssertionFailure {
+        let message = error.message.as_str();
+        let spans = std::iter::once(error.span).chain(error.trace.iter().map(|point| point.span));
+        AssertionFailure {
+            message: message.strip_prefix(assertions::FAILED).unwrap_or(message).into(),
+            position: spans.filter_map(|span| self.span_position(span)).next(),
+        }
+    }
+
+    /// Whether the main source is blank, not counting the prelude.
+    fn is_blank(&self) -> bool {
+        match self.source(self.main_id) {
+            Ok(source) => source.text()[self.prelude_len..].trim().is_empty(),
+            Err(_) => true,
+        }
+    }
+
+    /// Find the source position that produced the element at `click` on the
+    /// given (zero-based) page of a document compiled by this world.
+    fn trace(
+        &self,
+        document: &PagedDocument,
+        page: usize,
+        click: Point,
+    ) -> Option<SourcePosition> {
+        let frame = &document.pages.get(page)?.frame;
+        let Jump::File(id, offset) = typst_ide::jump_from_click(self, document, frame, click)?
+        else {
+            return None;
+        };
+        self.position(id, offset)
+    }
+
+    /// The source position an element's span points to.
+    fn span_position(&self, span: Span) -> Option<SourcePosition> {
+        let range = self.range(span)?;
+        self.position(span.id()?, range.start)
+    }
+
+    /// Turn a byte offset into a file into a line and column, both one-based.
+    fn position(&self, id: FileId, offset: usize) -> Option<SourcePosition> {
+        let source = self.source(id).ok()?;
+        let mut line = source.byte_to_line(offset)?;
+        if id == self.main_id {
+            // Lines are reported relative to the markup the caller passed in,
+            // so positions inside the prelude have no meaningful answer.
+            if offset < self.prelude_len {
+                return None;
             }
-            Err(errors) => {
-                let mut error_msg = "compile error:\n".to_string();
-                for error in errors.iter() {
-                    error_msg.push_str(&format!("{}\n", error.message));
-                }
-                Err(error_msg.into())
+            line -= source.byte_to_line(self.prelude_len)?;
+        }
+
+        Some(SourcePosition {
+            path: id.vpath().as_rootless_path().to_string_lossy().into_owned(),
+            line: line + 1,
+            column: source.byte_to_column(offset)? + 1,
+        })
+    }
+
+    /// The warnings of the last compilation: those of the compiler, followed
+    /// by the overflowing content found in the document.
+    fn warnings(&self, document: &PagedDocument) -> Vec<Warning> {
+        let compiler = self.warnings.iter().map(|warning| Warning {
+            kind: atoms::compiler(),
+            message: warning.message.to_string(),
+            page: None,
+            position: self.span_position(warning.span),
+        });
+
+        let overflows = overflow::detect(document).into_iter().map(|overflow| {
+            let (kind, container) = match overflow.is_page {
+                true => (atoms::page_overflow(), "the page"),
+                false => (atoms::container_overflow(), "its container"),
+            };
+            Warning {
+                kind,
+                message: format!("content overflows {container} by {:.1}pt", overflow.amount),
+                page: Some(overflow.page),
+                position: overflow.span.and_then(|span| self.span_position(span)),
             }
+        });
+
+        compiler.chain(overflows).collect()
+    }
+}
+
+/// Options of a compilation, decoded from the map built on the elixir side.
+#[derive(NifMap)]
+pub struct CompileOptions {
+    encryption: Option<encrypt::Encryption>,
+    max_pages: Option<usize>,
+    assertions: bool,
+    reject_empty: bool,
+    expect_uniform_pages: bool,
+    requirements: validate::Requirements,
+    debug: bool,
+}
+
+impl CompileOptions {
+    /// Check a compiled document against the options.
+    fn check(&self, world: &SystemWorld, document: &PagedDocument) -> Result<(), Error> {
+        // Checked first, as the other checks get expensive on huge documents.
+        self.check_page_limit(document)?;
+
+        if self.reject_empty
+            && (world.is_blank() || !document.pages.iter().any(|page| has_content(&page.frame)))
+        {
+            return Err(Error::EmptyDocument);
+        }
+
+        if self.expect_uniform_pages {
+            let sizes = pages::sizes(document);
+            if !pages::uniform(&sizes) {
+                return Err(Error::MixedPageSizes(sizes));
+            }
+        }
+
+        let failures = self.requirements.check(document);
+        if !failures.is_empty() {
+            return Err(Error::Validation(failures));
+        }
+        Ok(())
+    }
+
+    /// Check the number of pages of a compiled document against the limit.
+    ///
+    /// typst lays out page runs in parallel and offers no way to interrupt
+    /// the layout, so a runaway document is still laid out completely; the
+    /// limit only spares the checks and export after it.
+    fn check_page_limit(&self, document: &PagedDocument) -> Result<(), Error> {
+        match self.max_pages {
+            Some(limit) if document.pages.len() > limit => Err(Error::PageLimit(limit)),
+            _ => Ok(()),
         }
     }
 }
 
-/// A hash that is the same for all paths pointing to the same entity.
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
-struct PathHash(u128);
+/// Compile a project in `world` and export it as a PDF, checking the
+/// document against the options in between.
+pub(crate) fn compile_pdf(
+    world: &mut SystemWorld,
+    prelude: &str,
+    files: Vec<(String, Vec<u8>)>,
+    entry: &str,
+    options: &CompileOptions,
+) -> Result<(PagedDocument, Vec<u8>), Error> {
+    world.log.start(options.debug);
+    let document = world.compile_project(prelude, files, entry, options)?;
+    options.check(world, &document)?;
+    let pdf_bytes = export_pdf(&document, options)?;
+    Ok((document, pdf_bytes))
+}
 
-impl PathHash {
-    fn new(path: &Path) -> FileResult<Self> {
-        let f = |e| FileError::from_io(e, path);
-        let handle = Handle::from_path(path).map_err(f)?;
-        let mut state = SipHasher13::new();
-        handle.hash(&mut state);
-        Ok(Self(state.finish128().as_u128()))
+/// Compile a project in `world` and build a report about the document. Of
+/// the options, only those affecting the compilation itself apply.
+fn build_report<'a>(
+    env: Env<'a>,
+    world: &mut SystemWorld,
+    prelude: &str,
+    files: Vec<(String, Vec<u8>)>,
+    entry: &str,
+    options: &CompileOptions,
+    report: Report,
+) -> Result<(PagedDocument, Term<'a>), Error> {
+    if let Some(feature) = report.disabled_feature() {
+        return Err(Error::FeatureDisabled(feature));
     }
+    let prelude = format!("{prelude}{}", report.prelude());
+    world.log.start(false);
+    let document = world.compile_project(&prelude, files, entry, options)?;
+    options.check_page_limit(&document)?;
+    let term = report.build(env, world, &document)?;
+    Ok((document, term))
 }
 
+/// Export a compiled document as a PDF.
+fn export_pdf(
+    document: &PagedDocument,
+    options: &CompileOptions,
+) -> StrResult<Vec<u8>> {
+    let buffer = typst_pdf::pdf(document, &typst_pdf::PdfOptions::default())
+        .map_err(|e| format!("PDF export failed: {:?}", e))?;
+    match &options.encryption {
+        Some(encryption) => encrypt::encrypt(&buffer, encryption),
+        None => Ok(buffer),
+    }
+}
+
+/// A position in one of the world's sources, with one-based line and column.
+#[derive(NifMap, Clone)]
+struct SourcePosition {
+    path: String,
+    line: usize,
+    column: usize,
+}
+
+/// An assertion of a template that failed, with the position it failed at.
+#[derive(NifMap, Clone)]
+pub struct AssertionFailure {
+    message: String,
+    position: Option<SourcePosition>,
+}
+
+/// A warning about a compiled document, with the page and the source
+/// position of the element it concerns, where known.
+#[derive(NifMap)]
+struct Warning {
+    kind: Atom,
+    message: String,
+    page: Option<usize>,
+    position: Option<SourcePosition>,
+}
+
+/// Path of the main source when compiling a single piece of markup.
+pub(crate) const MAIN: &str = "MARKUP.typ";
+
 /// Read a file.
 fn read(path: &Path) -> FileResult<Vec<u8>> {
     let f = |e| FileError::from_io(e, path);
@@ -307,23 +802,246 @@ impl FontSearcher {
     }
 }
 
-#[rustler::nif]
-fn compile<'a>(markup: String, extra_fonts: Vec<String>) -> Result<String, String> {
-    let extra_fonts_paths: Vec<PathBuf> = extra_fonts.iter().map(|f| Path::new(f).into()).collect();
-   
-    let mut world = SystemWorld::new(".".into(), extra_fonts_paths.as_slice(), &[]);
-    let result = match world.compile(markup) {
-        Ok(pdf_bytes) => {
-            // the resulting string is not an utf-8 encoded string, but this is exactly what we
-            // want as we are passing a binary back to elixir
-            unsafe {
-                return Ok(String::from_utf8_unchecked(pdf_bytes));
+/// A world that outlives a single compilation, keeping its fonts and the
+/// last compiled document around so it can be inspected later on.
+///
+/// The state is dropped on shutdown, even while elixir still holds on to the
+/// resource.
+struct WorldResource {
+    state: Arc<Mutex<Option<WorldState>>>,
+}
+
+impl WorldResource {
+    /// Lock the world's state. A panic of an earlier user leaves nothing
+    /// behind that matters, as every compilation starts by resetting the
+    /// world.
+    fn lock(&self) -> MutexGuard<'_, Option<WorldState>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+struct WorldState {
+    world: SystemWorld,
+    document: Option<PagedDocument>,
+}
+
+/// All worlds ever created that are still alive, to free them on shutdown.
+static WORLDS: Mutex<Vec<Weak<Mutex<Option<WorldState>>>>> = Mutex::new(Vec::new());
+
+/// Options of a world, decoded from the map built on the elixir side.
+#[derive(NifMap)]
+pub struct WorldOptions {
+    extra_fonts: Vec<String>,
+    root: Option<String>,
+    cache: CachePolicy,
+}
+
+impl WorldOptions {
+    /// Create a world with these options.
+    pub(crate) fn build(&self) -> SystemWorld {
+        let font_paths: Vec<PathBuf> = self.extra_fonts.iter().map(PathBuf::from).collect();
+        let root = self.root.as_ref().map(PathBuf::from);
+        SystemWorld::new(root, self.cache, &font_paths, &[])
+    }
+}
+
+/// Pass a buffer (e.g. a PDF) back to elixir as a binary.
+pub(crate) fn binary(bytes: Vec<u8>) -> String {
+    // the resulting string is not an utf-8 encoded string, but this is exactly what we
+    // want as we are passing a binary back to elixir
+    unsafe { String::from_utf8_unchecked(bytes) }
+}
+
+/// Copy the files of a project passed in from elixir.
+fn project_files(files: Vec<(String, Binary)>) -> Vec<(String, Vec<u8>)> {
+    files
+        .into_iter()
+        .map(|(path, data)| (path, data.as_slice().to_vec()))
+        .collect()
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn compile<'a>(
+    prelude: String,
+    files: Vec<(String, Binary<'a>)>,
+    entry: String,
+    world_options: WorldOptions,
+    options: CompileOptions,
+) -> Result<String, Error> {
+    let _job = jobs::start()?;
+    catch_panic(|| {
+        let mut world = world_options.build();
+        let (_, pdf_bytes) =
+            compile_pdf(&mut world, &prelude, project_files(files), &entry, &options)?;
+        Ok(binary(pdf_bytes))
+    })
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn world_new(options: WorldOptions) -> Result<ResourceArc<WorldResource>, Error> {
+    let _job = jobs::start()?;
+    let world = catch_panic(|| Ok(options.build()))?;
+    let state = Arc::new(Mutex::new(Some(WorldState {
+        world,
+        document: None,
+    })));
+
+    let mut worlds = WORLDS.lock().unwrap();
+    worlds.retain(|world| world.strong_count() > 0);
+    worlds.push(Arc::downgrade(&state));
+
+    Ok(ResourceArc::new(WorldResource { state }))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn world_compile<'a>(
+    resource: ResourceArc<WorldResource>,
+    prelude: String,
+    files: Vec<(String, Binary<'a>)>,
+    entry: String,
+    options: CompileOptions,
+) -> Result<String, Error> {
+    let _job = jobs::start()?;
+    let mut state = resource.lock();
+    let state = state.as_mut().ok_or(Error::Shutdown)?;
+    state.document = None;
+    let (document, pdf_bytes) = catch_panic(|| {
+        compile_pdf(&mut state.world, &prelude, project_files(files), &entry, &options)
+    })?;
+    state.document = Some(document);
+    Ok(binary(pdf_bytes))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn compile_report<'a>(
+    env: Env<'a>,
+    prelude: String,
+    files: Vec<(String, Binary<'a>)>,
+    entry: String,
+    world_options: WorldOptions,
+    options: CompileOptions,
+    report: Report,
+) -> Result<Term<'a>, Error> {
+    let _job = jobs::start()?;
+    catch_panic(|| {
+        let mut world = world_options.build();
+        let files = project_files(files);
+        let (_, term) = build_report(env, &mut world, &prelude, files, &entry, &options, report)?;
+        Ok(term)
+    })
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn world_compile_report<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<WorldResource>,
+    prelude: String,
+    files: Vec<(String, Binary<'a>)>,
+    entry: String,
+    options: CompileOptions,
+    report: Report,
+) -> Result<Term<'a>, Error> {
+    let _job = jobs::start()?;
+    let mut state = resource.lock();
+    let state = state.as_mut().ok_or(Error::Shutdown)?;
+    state.document = None;
+    let (document, term) = catch_panic(|| {
+        let files = project_files(files);
+        build_report(env, &mut state.world, &prelude, files, &entry, &options, report)
+    })?;
+    state.document = Some(document);
+    Ok(term)
+}
+
+// Dirty, as a compilation of the same world holds its lock for as long as it
+// runs.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn world_trace(
+    resource: ResourceArc<WorldResource>,
+    page: usize,
+    x: f64,
+    y: f64,
+) -> Result<SourcePosition, Atom> {
+    let state = resource.lock();
+    let state = state.as_ref().ok_or(atoms::shutdown())?;
+    let document = state.document.as_ref().ok_or(atoms::no_document())?;
+    if page == 0 || page > document.pages.len() {
+        return Err(atoms::invalid_page());
+    }
+
+    let click = Point::new(Abs::pt(x), Abs::pt(y));
+    state
+        .world
+        .trace(document, page - 1, click)
+        .ok_or(atoms::not_found())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn world_warnings(resource: ResourceArc<WorldResource>) -> Result<Vec<Warning>, Atom> {
+    let state = resource.lock();
+    let state = state.as_ref().ok_or(atoms::shutdown())?;
+    let document = state.document.as_ref().ok_or(atoms::no_document())?;
+    Ok(state.world.warnings(document))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn world_log(resource: ResourceArc<WorldResource>) -> Result<Vec<debug::Event>, Atom> {
+    let state = resource.lock();
+    let state = state.as_ref().ok_or(atoms::shutdown())?;
+    Ok(state.world.log.events())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn world_cache_stats(resource: ResourceArc<WorldResource>) -> Result<CacheStats, Atom> {
+    let state = resource.lock();
+    let state = state.as_ref().ok_or(atoms::shutdown())?;
+    Ok(state.world.cache_stats())
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn shutdown(env: Env, timeout: u64) -> Term {
+    let remaining = jobs::shutdown(Duration::from_millis(timeout));
+
+    // Worlds still busy with a compilation that outlived the deadline are
+    // left alone; they are freed once elixir lets go of them.
+    for world in WORLDS.lock().unwrap().drain(..) {
+        if let Some(state) = world.upgrade() {
+            match state.try_lock() {
+                Ok(mut state) => *state = None,
+                Err(TryLockError::Poisoned(poisoned)) => *poisoned.into_inner() = None,
+                Err(TryLockError::WouldBlock) => {}
             }
-        },
-        Err(e) => Err(e.into())
-    };
+        }
+    }
+
+    match remaining {
+        0 => atoms::ok().encode(env),
+        n => (atoms::error(), (atoms::timeout(), n)).encode(env),
+    }
+}
 
-    result
+fn load(env: Env, _info: Term) -> bool {
+    rustler::resource!(WorldResource, env);
+    rustler::resource!(batch::BatchResource, env);
+    true
 }
 
-rustler::init!("Elixir.ExTypst.NIF", [compile]);
\ No newline at end of file
+rustler::init!(
+    "Elixir.ExTypst.NIF",
+    [
+        compile,
+        world_new,
+        world_compile,
+        compile_report,
+        world_compile_report,
+        world_trace,
+        world_warnings,
+        world_log,
+        world_cache_stats,
+        batch::batch_stream,
+        batch::batch_ack,
+        features::supported_features,
+        shutdown
+    ],
+    load = load
+);
@@ -0,0 +1,137 @@
+//! Detection of content overflowing its page or container.
+//!
+//! typst doesn't report overflow itself: content that doesn't fit into a
+//! block of fixed size, or an unbreakable block taller than the page, is just
+//! laid out beyond the bounds. This finds such content by comparing the
+//! extents of the items of every page and of every hard frame (which is what
+//! blocks, boxes and table cells produce) with the frame's size.
+//!
+//! Text is measured along its baseline only, since glyphs are expected to
+//! spill a bit over the edges of their lines.
+
+use typst::layout::{Frame, FrameItem, PagedDocument, Point, Size, Transform};
+use typst::syntax::Span;
+
+/// By how many points content may exceed its container before it counts as
+/// overflowing, to allow for rounding and protruding punctuation.
+const TOLERANCE: f64 = 1.0;
+
+/// Content overflowing its container.
+pub struct Overflow {
+    /// One-based page the container is on.
+    pub page: usize,
+    /// Whether the container is the page itself, rather than a block on it.
+    pub is_page: bool,
+    /// By how many points the content exceeds the container.
+    pub amount: f64,
+    /// Span of the overflowing element, if known.
+    pub span: Option<Span>,
+}
+
+/// Find all containers with overflowing content, reporting the element
+/// overflowing the most for each of them.
+pub fn detect(document: &PagedDocument) -> Vec<Overflow> {
+    let mut overflows = vec![];
+    for (i, page) in document.pages.iter().enumerate() {
+        check(&page.frame, i + 1, true, &mut overflows);
+    }
+    overflows
+}
+
+/// Check the content of a container, as well as of all containers in it.
+fn check(frame: &Frame, page: usize, is_page: bool, overflows: &mut Vec<Overflow>) {
+    let mut container = Container {
+        size: frame.size(),
+        worst: None,
+    };
+    container.collect(frame, Transform::identity(), page, overflows);
+
+    if let Some((amount, span)) = container.worst {
+        overflows.push(Overflow {
+            page,
+            is_page,
+            amount,
+            span,
+        });
+    }
+}
+
+struct Container {
+    size: Size,
+    worst: Option<(f64, Option<Span>)>,
+}
+
+impl Container {
+    /// Measure the items of `frame`, descending into soft groups, which are
+    /// part of this container, and checking hard ones on their own.
+    fn collect(
+        &mut self,
+        frame: &Frame,
+        ts: Transform,
+        page: usize,
+        overflows: &mut Vec<Overflow>,
+    ) {
+        for (pos, item) in frame.items() {
+            let ts = ts.pre_concat(Transform::translate(pos.x, pos.y));
+            match item {
+                FrameItem::Group(group) => {
+                    let ts = ts.pre_concat(group.transform);
+                    if group.frame.kind().is_hard() {
+                        self.measure(ts, group.frame.size(), first_span(&group.frame));
+                        check(&group.frame, page, false, overflows);
+                    } else {
+                        self.collect(&group.frame, ts, page, overflows);
+                    }
+                }
+                FrameItem::Text(text) => {
+                    let span = text.glyphs.first().map(|glyph| glyph.span.0);
+                    self.measure(ts, Size::with_x(text.width()), span);
+                }
+                FrameItem::Shape(shape, span) => {
+                    self.measure(ts, shape.geometry.bbox_size(), Some(*span));
+                }
+                FrameItem::Image(_, size, span) => self.measure(ts, *size, Some(*span)),
+                FrameItem::Link(..) | FrameItem::Tag(_) => {}
+            }
+        }
+    }
+
+    /// Measure by how much an item of the given size, placed with `ts`,
+    /// exceeds the container.
+    fn measure(&mut self, ts: Transform, size: Size, span: Option<Span>) {
+        let corners = [
+            Point::zero(),
+            Point::with_x(size.x),
+            Point::with_y(size.y),
+            size.to_point(),
+        ];
+
+        let amount = corners
+            .into_iter()
+            .map(|corner| {
+                let p = corner.transform(ts);
+                let x = (-p.x).max(p.x - self.size.x);
+                let y = (-p.y).max(p.y - self.size.y);
+                x.max(y).to_pt()
+            })
+            .fold(0.0, f64::max);
+
+        let worse = match self.worst {
+            Some((worst, _)) => amount > worst,
+            None => true,
+        };
+        if amount > TOLERANCE && worse {
+            self.worst = Some((amount, span));
+        }
+    }
+}
+
+/// The span of the first element in a frame that has one.
+fn first_span(frame: &Frame) -> Option<Span> {
+    frame.items().find_map(|(_, item)| match item {
+        FrameItem::Group(group) => first_span(&group.frame),
+        FrameItem::Text(text) => text.glyphs.first().map(|glyph| glyph.span.0),
+        FrameItem::Shape(_, span) | FrameItem::Image(_, _, span) => Some(*span),
+        FrameItem::Link(..) | FrameItem::Tag(_) => None,
+    })
+}
@@ -0,0 +1,53 @@
+//! Dimensions of the pages of compiled documents.
+
+use rustler::{Atom, NifMap};
+use typst::layout::PagedDocument;
+
+use crate::atoms;
+
+/// By how many points page sizes may differ while still counting as the same,
+/// to allow for rounding in unit conversions.
+const TOLERANCE: f64 = 0.01;
+
+/// The size of a page, in points.
+#[derive(NifMap, Clone)]
+pub struct PageSize {
+    page: usize,
+    width: f64,
+    height: f64,
+    orientation: Atom,
+}
+
+impl PageSize {
+    fn same(&self, other: &Self) -> bool {
+        (self.width - other.width).abs() <= TOLERANCE
+            && (self.height - other.height).abs() <= TOLERANCE
+    }
+}
+
+/// The sizes of all pages of a document.
+pub fn sizes(document: &PagedDocument) -> Vec<PageSize> {
+    document
+        .pages
+        .iter()
+        .enumerate()
+        .map(|(i, page)| {
+            let size = page.frame.size();
+            let (width, height) = (size.x.to_pt(), size.y.to_pt());
+            PageSize {
+                page: i + 1,
+                width,
+                height,
+                orientation: match width > height {
+                    true => atoms::landscape(),
+                    false => atoms::portrait(),
+                },
+            }
+        })
+        .collect()
+}
+
+/// Whether all pages have the same size.
+pub fn uniform(sizes: &[PageSize]) -> bool {
+    sizes.windows(2).all(|pair| pair[0].same(&pair[1]))
+}
@@ -0,0 +1,173 @@
+//! Export of documents as a JSON bundle for a browser-based viewer.
+//!
+//! The bundle has everything the viewer needs from one compilation: the size
+//! and rendering of each page, along with its links and its text (for
+//! selection and search), and the outline of the document.
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use rustler::{Atom, NifMap, NifUnitEnum};
+use serde_json::{json, Value};
+use typst::foundations::{NativeElement, Selector, StyleChain};
+use typst::introspection::{Introspector, Position};
+use typst::layout::{Frame, FrameItem, Page, PagedDocument, Point, Size, Transform};
+use typst::model::{Destination, HeadingElem};
+
+use crate::inspect::walk;
+use crate::{atoms, svg, Error};
+
+/// Options of a preview, decoded from the map built on the elixir side.
+#[derive(NifMap)]
+pub struct PreviewOptions {
+    format: PageFormat,
+    /// Resolution of PNG pages, in pixels per inch.
+    ppi: f64,
+}
+
+/// How pages are rendered.
+#[derive(NifUnitEnum, Clone, Copy)]
+pub enum PageFormat {
+    Svg,
+    Png,
+}
+
+impl PreviewOptions {
+    /// The feature the page format needs, if it was compiled out of the NIF.
+    pub fn disabled_feature(&self) -> Option<Atom> {
+        match self.format {
+            PageFormat::Svg if !cfg!(feature = "svg") => Some(atoms::svg_export()),
+            PageFormat::Png if !cfg!(feature = "png") => Some(atoms::png_export()),
+            _ => None,
+        }
+    }
+}
+
+/// Export a document as a JSON bundle. Coordinates and sizes are in points,
+/// measured from the top left corner of the page.
+pub fn export(document: &PagedDocument, options: &PreviewOptions) -> Result<String, Error> {
+    let introspector = &document.introspector;
+    let pages = document
+        .pages
+        .iter()
+        .map(|page| bundle_page(page, introspector, options))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let bundle = json!({
+        "pages": pages,
+        "outline": outline(introspector),
+    });
+    Ok(bundle.to_string())
+}
+
+/// A page of the bundle.
+fn bundle_page(
+    page: &Page,
+    introspector: &Introspector,
+    options: &PreviewOptions,
+) -> Result<Value, Error> {
+    let size = page.frame.size();
+    let mut bundle = json!({
+        "width": size.x.to_pt(),
+        "height": size.y.to_pt(),
+        "links": links(&page.frame, introspector),
+        "text": text(&page.frame),
+    });
+
+    match options.format {
+        PageFormat::Svg => bundle["svg"] = svg::render(page)?.into(),
+        PageFormat::Png => bundle["png"] = BASE64_STANDARD.encode(png(page, options.ppi)?).into(),
+    }
+    Ok(bundle)
+}
+
+/// Render a page as a PNG.
+#[cfg(feature = "png")]
+fn png(page: &Page, ppi: f64) -> Result<Vec<u8>, Error> {
+    let pixmap = typst_render::render(page, (ppi / 72.0) as f32);
+    pixmap
+        .encode_png()
+        .map_err(|e| Error::Message(ecow::eco_format!("failed to encode PNG: {e}")))
+}
+
+/// Render a page as a PNG, in a build without PNG export. Reports reject it
+/// before compiling anything already, see `Report::disabled_feature`.
+#[cfg(not(feature = "png"))]
+fn png(_: &Page, _: f64) -> Result<Vec<u8>, Error> {
+    Err(Error::FeatureDisabled(atoms::png_export()))
+}
+
+/// The links of a page, with either the URL or the position they point to.
+fn links(frame: &Frame, introspector: &Introspector) -> Vec<Value> {
+    let mut links = vec![];
+    walk(frame, Transform::identity(), &mut |ts, item| {
+        let FrameItem::Link(destination, size) = item else {
+            return;
+        };
+
+        let mut link = rect(ts, *size);
+        match destination {
+            Destination::Url(url) => link["url"] = url.as_str().into(),
+            Destination::Position(position) => link["target"] = target(*position),
+            Destination::Location(location) => {
+                link["target"] = target(introspector.position(*location))
+            }
+        }
+        links.push(link);
+    });
+    links
+}
+
+/// The text of a page, by runs of text. Their position is the start of their
+/// baseline, and their height the font size.
+fn text(frame: &Frame) -> Vec<Value> {
+    let mut text = vec![];
+    walk(frame, Transform::identity(), &mut |ts, item| {
+        let FrameItem::Text(run) = item else {
+            return;
+        };
+
+        let mut span = rect(ts, Size::new(run.width(), run.size));
+        span["text"] = run.text.as_str().into();
+        text.push(span);
+    });
+    text
+}
+
+/// The headings of the document that are part of its outline.
+fn outline(introspector: &Introspector) -> Vec<Value> {
+    let styles = StyleChain::default();
+    introspector
+        .query(&Selector::Elem(HeadingElem::elem(), None))
+        .iter()
+        .filter_map(|elem| {
+            let heading = elem.to_packed::<HeadingElem>()?;
+            if !heading.outlined(styles) {
+                return None;
+            }
+
+            let mut entry = target(introspector.position(elem.location()?));
+            entry["title"] = heading.body.plain_text().as_str().into();
+            entry["level"] = heading.resolve_level(styles).get().into();
+            Some(entry)
+        })
+        .collect()
+}
+
+/// Where something with the given size is placed by `ts`.
+fn rect(ts: Transform, size: Size) -> Value {
+    json!({
+        "x": ts.tx.to_pt(),
+        "y": ts.ty.to_pt(),
+        "width": size.x.to_pt(),
+        "height": size.y.to_pt(),
+    })
+}
+
+/// A position in the document, with a one-based page.
+fn target(position: Position) -> Value {
+    let Point { x, y } = position.point;
+    json!({
+        "page": position.page.get(),
+        "x": x.to_pt(),
+        "y": y.to_pt(),
+    })
+}
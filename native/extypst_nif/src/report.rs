@@ -0,0 +1,90 @@
+//! Reports about the content of compiled documents, so that what ended up in
+//! a document can be checked without picking the PDF apart, and exports to
+//! formats other than PDF.
+
+use rustler::{Atom, Decoder, Encoder, Env, NifResult, Term};
+use typst::layout::PagedDocument;
+
+use crate::preview::{self, PreviewOptions};
+use crate::svg::{self, SvgOptions};
+use crate::values::{self, Encoded};
+use crate::{atoms, fonts, images, pages, tables, Error, SystemWorld};
+
+/// What to report about a document, decoded from an atom, or a tuple of an
+/// atom and options.
+pub enum Report {
+    Tables,
+    Images,
+    Fonts,
+    Pages,
+    /// The values of `metadata` elements, optionally only with a label.
+    Metadata(Option<String>),
+    Svg(SvgOptions),
+    Preview(PreviewOptions),
+}
+
+impl<'a> Decoder<'a> for Report {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        if let Ok((tag, options)) = term.decode::<(Atom, Term)>() {
+            if tag == atoms::svg() {
+                return Ok(Self::Svg(options.decode()?));
+            } else if tag == atoms::preview() {
+                return Ok(Self::Preview(options.decode()?));
+            } else if tag == atoms::metadata() {
+                return Ok(Self::Metadata(options.decode()?));
+            }
+        } else if let Ok(atom) = term.decode::<Atom>() {
+            if atom == atoms::tables() {
+                return Ok(Self::Tables);
+            } else if atom == atoms::images() {
+                return Ok(Self::Images);
+            } else if atom == atoms::fonts() {
+                return Ok(Self::Fonts);
+            } else if atom == atoms::pages() {
+                return Ok(Self::Pages);
+            }
+        }
+        Err(rustler::Error::BadArg)
+    }
+}
+
+impl Report {
+    /// Markup compiled between the prelude and the document, to make what
+    /// the report is about introspectable.
+    pub fn prelude(&self) -> &'static str {
+        match self {
+            Self::Tables => tables::PRELUDE,
+            _ => "",
+        }
+    }
+
+    /// The feature the report needs, if it was compiled out of the NIF.
+    pub fn disabled_feature(&self) -> Option<Atom> {
+        match self {
+            Self::Svg(_) if !cfg!(feature = "svg") => Some(atoms::svg_export()),
+            Self::Preview(options) => options.disabled_feature(),
+            _ => None,
+        }
+    }
+
+    /// Build the report about a document compiled with [`Self::prelude`].
+    pub fn build<'a>(
+        &self,
+        env: Env<'a>,
+        world: &SystemWorld,
+        document: &PagedDocument,
+    ) -> Result<Term<'a>, Error> {
+        Ok(match self {
+            Self::Tables => tables::extract(document).encode(env),
+            Self::Images => images::extract(world, document).encode(env),
+            Self::Fonts => fonts::report(world, document).encode(env),
+            Self::Pages => pages::sizes(document).encode(env),
+            Self::Metadata(label) => {
+                let values = values::metadata(document, label.as_deref());
+                values.iter().map(Encoded).collect::<Vec<_>>().encode(env)
+            }
+            Self::Svg(options) => svg::export(document, options)?.encode(env),
+            Self::Preview(options) => preview::export(document, options)?.encode(env),
+        })
+    }
+}
@@ -0,0 +1,126 @@
+//! Export of documents as SVGs, one per page.
+//!
+//! typst inlines images into SVGs as base64-encoded data URLs, which makes
+//! pages with large images slow to transfer and to update in a browser. On
+//! request, such images are replaced by references to assets returned next
+//! to the pages, which can be served (and cached) on their own.
+
+use std::collections::HashMap;
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use rustler::NifMap;
+use typst::layout::{Page, PagedDocument};
+
+use crate::images::sha256;
+use crate::{binary, Error};
+
+/// Options of an SVG export, decoded from the map built on the elixir side.
+#[derive(NifMap)]
+pub struct SvgOptions {
+    external_images: Option<ExternalImages>,
+}
+
+/// Which images to turn into external assets, and how to refer to them.
+#[derive(NifMap)]
+pub struct ExternalImages {
+    /// Size in bytes from which on images become external assets.
+    min_size: usize,
+    /// Prepended to the names of assets to refer to them from the SVGs.
+    url_prefix: String,
+}
+
+/// The pages of a document as SVGs, along with the assets they refer to, by
+/// name.
+#[derive(NifMap)]
+pub struct SvgExport {
+    pages: Vec<String>,
+    assets: HashMap<String, String>,
+}
+
+/// Export all pages of a document.
+pub fn export(document: &PagedDocument, options: &SvgOptions) -> Result<SvgExport, Error> {
+    let mut assets = HashMap::new();
+    let pages = document
+        .pages
+        .iter()
+        .map(|page| {
+            let svg = render(page)?;
+            Ok(match &options.external_images {
+                Some(external) => external.extract(&svg, &mut assets),
+                None => svg,
+            })
+        })
+        .collect::<Result<_, Error>>()?;
+    Ok(SvgExport { pages, assets })
+}
+
+/// Render a page as an SVG.
+#[cfg(feature = "svg")]
+pub fn render(page: &Page) -> Result<String, Error> {
+    Ok(typst_svg::svg(page))
+}
+
+/// Render a page as an SVG, in a build without SVG export. Reports reject it
+/// before compiling anything already, see `Report::disabled_feature`.
+#[cfg(not(feature = "svg"))]
+pub fn render(_: &Page) -> Result<String, Error> {
+    Err(Error::FeatureDisabled(crate::atoms::svg_export()))
+}
+
+impl ExternalImages {
+    /// Replace the data URLs of large images in an SVG by references to
+    /// assets, adding those to `assets`.
+    fn extract(&self, svg: &str, assets: &mut HashMap<String, String>) -> String {
+        const HREF: &str = "href=\"";
+
+        let mut extracted = String::with_capacity(svg.len());
+        let mut rest = svg;
+        while let Some(start) = rest.find("href=\"data:") {
+            let url_start = start + HREF.len();
+            let Some(len) = rest[url_start..].find('"') else {
+                break;
+            };
+            let url = &rest[url_start..url_start + len];
+
+            extracted.push_str(&rest[..url_start]);
+            match self.asset(url) {
+                Some((name, data)) => {
+                    extracted.push_str(&self.url_prefix);
+                    extracted.push_str(&name);
+                    assets.insert(name, binary(data));
+                }
+                None => extracted.push_str(url),
+            }
+            rest = &rest[url_start + len..];
+        }
+
+        extracted.push_str(rest);
+        extracted
+    }
+
+    /// Decode a data URL into an asset, named by the hash of its data, if it
+    /// is large enough.
+    fn asset(&self, url: &str) -> Option<(String, Vec<u8>)> {
+        let (header, payload) = url.strip_prefix("data:")?.split_once(',')?;
+        let mime = header.strip_suffix(";base64")?;
+        // Skip decoding images that are too small anyway.
+        if payload.len() / 4 * 3 < self.min_size {
+            return None;
+        }
+
+        let data = BASE64_STANDARD.decode(payload).ok()?;
+        if data.len() < self.min_size {
+            return None;
+        }
+
+        let extension = match mime {
+            "image/png" => "png",
+            "image/jpeg" | "image/jpg" => "jpg",
+            "image/gif" => "gif",
+            "image/webp" => "webp",
+            "image/svg+xml" => "svg",
+            _ => "bin",
+        };
+        Some((format!("{}.{extension}", &sha256(&data)[..16]), data))
+    }
+}
@@ -0,0 +1,107 @@
+//! Extraction of the tables of compiled documents.
+//!
+//! Tables leave no trace in the laid out frames, so documents are compiled
+//! with show rules tagging every table cell with its table, position and
+//! body, which are then queried from the introspector.
+
+use std::collections::{BTreeMap, HashMap};
+
+use rustler::NifMap;
+use typst::foundations::{Content, Label, Selector, Value};
+use typst::introspection::MetadataElem;
+use typst::layout::PagedDocument;
+use typst_utils::PicoStr;
+
+/// Show rules tagging table cells. Tables are numbered in the order they
+/// start in, and a stack of open tables attributes the cells after a nested
+/// table to the table around it.
+pub const PRELUDE: &str = r#"
+#let ex-typst-tables = counter("ex-typst-tables")
+#let ex-typst-open-tables = state("ex-typst-open-tables", ())
+#show table: it => {
+  ex-typst-tables.step()
+  context ex-typst-open-tables.update(open => open + ex-typst-tables.get())
+  it
+  ex-typst-open-tables.update(open => open.slice(0, -1))
+}
+#show table.cell: it => {
+  context [#metadata((
+    table: ex-typst-open-tables.get().last(),
+    x: it.x,
+    y: it.y,
+    body: it.body,
+  )) <ex-typst-cell>]
+  it
+}
+"#;
+
+/// The part of a table on one page, with the text of its cells by row and
+/// column. Cells covered by a cell spanning several rows or columns are
+/// empty.
+#[derive(NifMap)]
+pub struct Table {
+    /// One-based number of the table in the document, shared by all parts of
+    /// a table broken across pages.
+    table: usize,
+    page: usize,
+    rows: Vec<Vec<String>>,
+}
+
+struct Cell {
+    table: usize,
+    page: usize,
+    x: usize,
+    y: usize,
+    text: String,
+}
+
+/// Extract the tables of a document compiled with the [`PRELUDE`], one entry
+/// for each page a table is on.
+pub fn extract(document: &PagedDocument) -> Vec<Table> {
+    let Some(label) = Label::new(PicoStr::intern("ex-typst-cell")) else {
+        return vec![];
+    };
+
+    let introspector = &document.introspector;
+    let cells = introspector
+        .query(&Selector::Label(label))
+        .iter()
+        .filter_map(|elem| {
+            let Value::Dict(dict) = &elem.to_packed::<MetadataElem>()?.value else {
+                return None;
+            };
+            let field = |key: &str| dict.get(key).ok().cloned();
+            Some(Cell {
+                table: field("table")?.cast().ok()?,
+                page: introspector.position(elem.location()?).page.get(),
+                x: field("x")?.cast().ok()?,
+                y: field("y")?.cast().ok()?,
+                text: field("body")?.cast::<Content>().ok()?.plain_text().into(),
+            })
+        });
+
+    let mut columns: HashMap<usize, usize> = HashMap::new();
+    let mut parts: BTreeMap<(usize, usize), Vec<Cell>> = BTreeMap::new();
+    for cell in cells {
+        let width = columns.entry(cell.table).or_default();
+        *width = (*width).max(cell.x + 1);
+        parts.entry((cell.table, cell.page)).or_default().push(cell);
+    }
+
+    parts
+        .into_iter()
+        .map(|((table, page), cells)| {
+            let width = columns[&table];
+            let mut rows = BTreeMap::new();
+            for cell in cells {
+                let row = rows.entry(cell.y).or_insert_with(|| vec![String::new(); width]);
+                row[cell.x] = cell.text;
+            }
+            Table {
+                table,
+                page,
+                rows: rows.into_values().collect(),
+            }
+        })
+        .collect()
+}
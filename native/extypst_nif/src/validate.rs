@@ -0,0 +1,80 @@
+//! Sanity checks of compiled documents, so that documents missing vital
+//! parts never leave the pipeline.
+
+use rustler::{Encoder, Env, NifMap, Term};
+use typst::foundations::{Label, Selector};
+use typst::layout::PagedDocument;
+use typst_utils::PicoStr;
+
+use crate::atoms;
+use crate::inspect;
+
+/// What a document must contain to be considered valid.
+#[derive(NifMap)]
+pub struct Requirements {
+    min_pages: usize,
+    labels: Vec<String>,
+    text: Vec<String>,
+}
+
+/// A requirement a document failed to meet.
+#[derive(Clone)]
+pub enum Failure {
+    MinPages { expected: usize, actual: usize },
+    MissingLabel(String),
+    MissingText(String),
+}
+
+impl Encoder for Failure {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        match self {
+            Self::MinPages { expected, actual } => {
+                (atoms::min_pages(), expected, actual).encode(env)
+            }
+            Self::MissingLabel(label) => (atoms::missing_label(), label).encode(env),
+            Self::MissingText(text) => (atoms::missing_text(), text).encode(env),
+        }
+    }
+}
+
+impl Requirements {
+    /// Check a document against the requirements, returning all of those it
+    /// failed to meet.
+    pub fn check(&self, document: &PagedDocument) -> Vec<Failure> {
+        let mut failures = vec![];
+
+        let pages = document.pages.len();
+        if pages < self.min_pages {
+            failures.push(Failure::MinPages {
+                expected: self.min_pages,
+                actual: pages,
+            });
+        }
+
+        for name in &self.labels {
+            let found = Label::new(PicoStr::intern(name)).is_some_and(|label| {
+                !document.introspector.query(&Selector::Label(label)).is_empty()
+            });
+            if !found {
+                failures.push(Failure::MissingLabel(name.clone()));
+            }
+        }
+
+        if !self.text.is_empty() {
+            let text = normalize(&inspect::document_text(document));
+            for snippet in &self.text {
+                if !text.contains(&normalize(snippet)) {
+                    failures.push(Failure::MissingText(snippet.clone()));
+                }
+            }
+        }
+
+        failures
+    }
+}
+
+/// Collapse all whitespace into single spaces, so that snippets match
+/// regardless of where lines were broken.
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
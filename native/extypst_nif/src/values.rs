@@ -0,0 +1,110 @@
+//! Conversion of typst values into native elixir terms.
+
+use rustler::types::atom;
+use rustler::{Encoder, Env, Term};
+use typst::foundations::{Datetime, Label, NativeElement, Repr, Selector, Value};
+use typst::introspection::MetadataElem;
+use typst::layout::PagedDocument;
+use typst_utils::PicoStr;
+
+use crate::atoms;
+
+/// A typst value, encoded as the closest native elixir term:
+///
+/// - `none` and `auto` become `nil` and `:auto`
+/// - booleans, integers, floats and strings become their elixir counterpart,
+///   except for non-finite floats, which elixir lacks, becoming `:infinity`,
+///   `:neg_infinity` and `:nan`
+/// - decimals become `{:decimal, string}`, so that no precision is lost
+/// - labels and content become strings, the latter by its plain text
+/// - arrays become lists, and dictionaries maps with string keys
+/// - dates, times and datetimes become `Date`, `Time` and `NaiveDateTime`
+///   structs
+/// - all other values (lengths, colors, functions, ...) become their repr
+pub struct Encoded<'v>(pub &'v Value);
+
+impl Encoder for Encoded<'_> {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        match self.0 {
+            Value::None => atom::nil().encode(env),
+            Value::Auto => atoms::auto().encode(env),
+            Value::Bool(bool) => bool.encode(env),
+            Value::Int(int) => int.encode(env),
+            Value::Float(float) => encode_float(env, *float),
+            Value::Decimal(decimal) => (atoms::decimal(), decimal.to_string()).encode(env),
+            Value::Str(str) => str.as_str().encode(env),
+            Value::Label(label) => label.resolve().as_str().encode(env),
+            Value::Content(content) => content.plain_text().as_str().encode(env),
+            Value::Array(array) => array.iter().map(Encoded).collect::<Vec<_>>().encode(env),
+            Value::Dict(dict) => dict.iter().fold(Term::map_new(env), |map, (key, value)| {
+                map.map_put(key.as_str(), Encoded(value)).unwrap()
+            }),
+            Value::Datetime(datetime) => encode_datetime(env, datetime),
+            other => other.repr().as_str().encode(env),
+        }
+    }
+}
+
+/// Encode a float, with atoms for the values elixir floats can't hold.
+fn encode_float(env: Env, float: f64) -> Term {
+    if float.is_nan() {
+        atoms::nan().encode(env)
+    } else if float == f64::INFINITY {
+        atoms::infinity().encode(env)
+    } else if float == f64::NEG_INFINITY {
+        atoms::neg_infinity().encode(env)
+    } else {
+        float.encode(env)
+    }
+}
+
+/// Encode a datetime as the elixir struct with the fields it has.
+fn encode_datetime<'a>(env: Env<'a>, datetime: &Datetime) -> Term<'a> {
+    let module = match datetime {
+        Datetime::Date(_) => atoms::date(),
+        Datetime::Time(_) => atoms::time(),
+        Datetime::Datetime(_) => atoms::naive_datetime(),
+    };
+
+    let fields = [
+        (atoms::year(), datetime.year().map(|year| year.encode(env))),
+        (atoms::month(), datetime.month().map(|month| month.encode(env))),
+        (atoms::day(), datetime.day().map(|day| day.encode(env))),
+        (atoms::hour(), datetime.hour().map(|hour| hour.encode(env))),
+        (atoms::minute(), datetime.minute().map(|minute| minute.encode(env))),
+        (atoms::second(), datetime.second().map(|second| second.encode(env))),
+    ];
+
+    let mut map = Term::map_new(env)
+        .map_put(atoms::__struct__(), module)
+        .and_then(|map| map.map_put(atoms::calendar(), atoms::calendar_iso()))
+        .unwrap();
+    for (key, value) in fields {
+        if let Some(value) = value {
+            map = map.map_put(key, value).unwrap();
+        }
+    }
+    if datetime.hour().is_some() {
+        map = map.map_put(atoms::microsecond(), (0, 0)).unwrap();
+    }
+    map
+}
+
+/// The values of the `metadata` elements of a document, in document order,
+/// optionally only of those with the given label.
+pub fn metadata(document: &PagedDocument, label: Option<&str>) -> Vec<Value> {
+    let selector = match label {
+        Some(label) => match Label::new(PicoStr::intern(label)) {
+            Some(label) => Selector::Label(label),
+            None => return vec![],
+        },
+        None => Selector::Elem(MetadataElem::elem(), None),
+    };
+
+    document
+        .introspector
+        .query(&selector)
+        .iter()
+        .filter_map(|elem| Some(elem.to_packed::<MetadataElem>()?.value.clone()))
+        .collect()
+}
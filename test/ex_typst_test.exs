@@ -1,6 +1,7 @@
 defmodule ExTypstTest do
   use ExUnit.Case
   doctest ExTypst
+  doctest ExTypst.Batch
 
   test "render_to_string/2" do
     users = [
@@ -33,4 +34,559 @@ defmodule ExTypstTest do
 
     assert ExTypst.render_to_string(content, users: formatted_users) == expected
   end
+
+  describe "trace/4" do
+    setup do
+      {:ok, world} = ExTypst.new_world()
+      %{world: world}
+    end
+
+    test "requires a compiled document", %{world: world} do
+      assert ExTypst.trace(world, 1, 75, 75) == {:error, :no_document}
+    end
+
+    test "maps lines back to the rendered markup", %{world: world} do
+      {:ok, _pdf} = ExTypst.render_to_pdf("\n\nSome text", [], world: world)
+
+      assert {:ok, %{line: 3}} = ExTypst.trace(world, 1, 75, 75)
+      assert ExTypst.trace(world, 2, 75, 75) == {:error, :invalid_page}
+    end
+  end
+
+  describe "warnings/1" do
+    setup do
+      {:ok, world} = ExTypst.new_world()
+      %{world: world}
+    end
+
+    test "requires a compiled document", %{world: world} do
+      assert ExTypst.warnings(world) == {:error, :no_document}
+    end
+
+    test "is empty for content that fits", %{world: world} do
+      {:ok, _pdf} = ExTypst.render_to_pdf("#table(columns: 2)[a][b]", [], world: world)
+
+      assert ExTypst.warnings(world) == {:ok, []}
+    end
+
+    test "reports unbreakable blocks taller than the page", %{world: world} do
+      markup = "Intro\n\n#block(breakable: false, height: 40cm)[Tall]"
+      {:ok, _pdf} = ExTypst.render_to_pdf(markup, [], world: world)
+
+      assert {:ok, [%{kind: :page_overflow, page: page, position: %{line: 3}}]} =
+               ExTypst.warnings(world)
+
+      assert page in [1, 2]
+    end
+
+    test "includes compiler warnings", %{world: world} do
+      {:ok, _pdf} = ExTypst.render_to_pdf("#text(font: \"Nonexistent\")[Hi]", [], world: world)
+
+      assert {:ok, [%{kind: :compiler, position: %{line: 1}}]} = ExTypst.warnings(world)
+    end
+  end
+
+  describe "tables/3" do
+    test "numbers tables in document order" do
+      markup = "#table[A]\n#figure(table(columns: 2)[B][C])"
+
+      assert {:ok, [%{table: 1, rows: [["A"]]}, %{table: 2, rows: [["B", "C"]]}]} =
+               ExTypst.tables(markup)
+    end
+
+    test "splits tables broken across pages" do
+      markup = "#table(..range(50).map(n => [Row #n]))"
+
+      assert {:ok, [%{table: 1, page: 1} = first, %{table: 1, page: 2} = second]} =
+               ExTypst.tables(markup)
+
+      assert hd(first.rows) == ["Row 0"]
+      assert length(first.rows) + length(second.rows) == 50
+    end
+
+    test "leaves cells covered by spanning cells empty" do
+      markup = "#table(columns: 2, table.cell(colspan: 2)[Wide], [A], [B])"
+
+      assert {:ok, [%{rows: [["Wide", ""], ["A", "B"]]}]} = ExTypst.tables(markup)
+    end
+
+    test "attributes cells after a nested table to the outer table" do
+      markup = "#table(columns: 2, table[Inner], [Outer])"
+
+      assert {:ok, [%{table: 1, rows: [[_, "Outer"]]}, %{table: 2, rows: [["Inner"]]}]} =
+               ExTypst.tables(markup)
+    end
+  end
+
+  describe "images/3" do
+    @describetag :tmp_dir
+
+    setup %{tmp_dir: root} do
+      svg = ~s(<svg xmlns="http://www.w3.org/2000/svg" width="30" height="15"/>)
+      File.write!(Path.join(root, "signature.svg"), svg)
+      %{root: root, svg: svg}
+    end
+
+    test "reports path, hash and placement of images", %{root: root, svg: svg} do
+      markup = ~s|#pagebreak()\n#place(dx: 2cm)[#image("signature.svg", width: 3cm)]|
+
+      assert {:ok, [image]} = ExTypst.images(markup, [], root: root)
+      assert %{page: 2, path: "signature.svg", natural_width: 30.0, position: %{line: 2}} = image
+      assert image.sha256 == Base.encode16(:crypto.hash(:sha256, svg), case: :lower)
+      assert_in_delta image.width, 85.04, 0.01
+      assert_in_delta image.height, 42.52, 0.01
+    end
+
+    test "is empty without images" do
+      assert ExTypst.images("Just text") == {:ok, []}
+    end
+  end
+
+  describe "font_usage/3" do
+    test "counts glyphs per face" do
+      markup = ~s|#set text(font: "IBM Plex Sans")\nSome *bold* text|
+
+      assert {:ok, [regular, bold]} = ExTypst.font_usage(markup)
+      assert %{family: "IBM Plex Sans", style: :normal, weight: 400} = regular
+      assert %{family: "IBM Plex Sans", weight: 700, glyphs: 4, distinct_glyphs: 4} = bold
+      assert Path.basename(bold.path) == "IBMPlexSans-Bold.ttf"
+    end
+
+    test "reports fallback faces" do
+      markup = ~s|#set text(font: "IBM Plex Sans", fallback: true)\nShalom שלום|
+
+      assert {:ok, [%{family: "IBM Plex Sans"}, %{family: fallback}]} =
+               ExTypst.font_usage(markup)
+
+      assert fallback != "IBM Plex Sans"
+    end
+  end
+
+  describe "render_to_svg/3" do
+    @describetag :tmp_dir
+
+    setup %{tmp_dir: root} do
+      File.write!(Path.join(root, "small.svg"), ~s(<svg xmlns="http://www.w3.org/2000/svg"/>))
+      %{root: root}
+    end
+
+    test "inlines images by default", %{root: root} do
+      assert {:ok, %{pages: [page], assets: assets}} =
+               ExTypst.render_to_svg(~s|#image("small.svg", width: 1cm)|, [], root: root)
+
+      assert page =~ "data:image/svg+xml;base64,"
+      assert assets == %{}
+    end
+
+    test "refers to large images as external assets", %{root: root} do
+      markup = ~s|#image("small.svg", width: 1cm)\n#pagebreak()\n#image("small.svg")|
+      opts = [root: root, external_images: [url_prefix: "/assets/"]]
+
+      assert {:ok, %{pages: [first, second], assets: assets}} =
+               ExTypst.render_to_svg(markup, [], opts)
+
+      assert [{name, data}] = Map.to_list(assets)
+      assert name =~ ~r/^[0-9a-f]{16}\.svg$/
+      assert data == File.read!(Path.join(root, "small.svg"))
+      assert first =~ ~s(href="/assets/#{name}")
+      assert second =~ ~s(href="/assets/#{name}")
+      refute first =~ "data:"
+    end
+
+    test "keeps images below the minimum size inline", %{root: root} do
+      opts = [root: root, external_images: [min_size: 1_000]]
+
+      assert {:ok, %{pages: [page], assets: assets}} =
+               ExTypst.render_to_svg(~s|#image("small.svg")|, [], opts)
+
+      assert page =~ "data:image/svg+xml;base64,"
+      assert assets == %{}
+    end
+  end
+
+  describe "preview_bundle/3" do
+    test "resolves links to their targets" do
+      markup = """
+      #link("https://typst.app")[Typst]
+      #pagebreak()
+      = Target <target>
+      #link(<target>)[Back]
+      """
+
+      assert {:ok, json} = ExTypst.preview_bundle(markup)
+      assert %{"pages" => [first, second]} = Jason.decode!(json)
+      assert [%{"url" => "https://typst.app"}] = first["links"]
+      assert [%{"target" => %{"page" => 2}}] = second["links"]
+    end
+
+    test "renders pages as PNG" do
+      assert {:ok, json} = ExTypst.preview_bundle("Hey", [], page_format: :png, ppi: 72)
+      assert %{"pages" => [page]} = Jason.decode!(json)
+
+      assert <<0x89, "PNG", _::binary>> = Base.decode64!(page["png"])
+      refute Map.has_key?(page, "svg")
+    end
+
+    test "leaves headings out of the outline on request" do
+      markup = "= Shown\n#heading(outlined: false)[Hidden]"
+
+      assert {:ok, json} = ExTypst.preview_bundle(markup)
+      assert %{"outline" => [%{"title" => "Shown", "level" => 1}]} = Jason.decode!(json)
+    end
+  end
+
+  describe "query_metadata/3" do
+    test "decodes values into native terms" do
+      markup = """
+      #metadata(none)
+      #metadata((1, 2.5, "three", true, auto))
+      #metadata(decimal("12.50"))
+      #metadata([*Total*: 12])
+      #metadata(datetime(hour: 13, minute: 5, second: 0))
+      #metadata(datetime(year: 2024, month: 5, day: 1, hour: 13, minute: 5, second: 0))
+      #metadata(12pt)
+      """
+
+      assert ExTypst.query_metadata(markup) ==
+               {:ok,
+                [
+                  nil,
+                  [1, 2.5, "three", true, :auto],
+                  {:decimal, "12.50"},
+                  "Total: 12",
+                  ~T[13:05:00],
+                  ~N[2024-05-01 13:05:00],
+                  "12pt"
+                ]}
+    end
+
+    test "only returns metadata with the given label" do
+      markup = ~s|#metadata("a") <a>\n#metadata("b") <b>\n#metadata("c") <a>|
+
+      assert ExTypst.query_metadata(markup, [], label: "a") == {:ok, ["a", "c"]}
+      assert ExTypst.query_metadata(markup, [], label: "missing") == {:ok, []}
+    end
+
+    test "encodes non-finite floats as atoms" do
+      markup = "#metadata((calc.inf, -calc.inf, float.nan, 1.5))"
+
+      assert ExTypst.query_metadata(markup) == {:ok, [[:infinity, :neg_infinity, :nan, 1.5]]}
+    end
+  end
+
+  describe "compile_project/2" do
+    test "resolves files relative to the importing file" do
+      files = %{
+        "main.typ" => ~s(#include "chapters/one.typ"),
+        "chapters/one.typ" => ~s(#let data = read("../data.txt")\n= #data),
+        "data.txt" => "Chapter one"
+      }
+
+      assert {:ok, pdf} = ExTypst.compile_project(files, entry: "main.typ")
+      assert is_binary(pdf)
+    end
+
+    test "fails on a missing entry file" do
+      assert {:error, "entry file not found: main.typ"} =
+               ExTypst.compile_project(%{"other.typ" => "Hey"}, entry: "main.typ")
+    end
+
+    test "reports the file of traced elements" do
+      {:ok, world} = ExTypst.new_world()
+      files = %{"main.typ" => ~s(#include "body.typ"), "body.typ" => "Some text"}
+
+      {:ok, _pdf} = ExTypst.compile_project(files, entry: "main.typ", world: world)
+
+      assert {:ok, %{path: "body.typ", line: 1}} = ExTypst.trace(world, 1, 75, 75)
+    end
+  end
+
+  describe "stream_pdfs/3" do
+    test "waits for each acknowledgment before producing the next document" do
+      {:ok, batch} = ExTypst.stream_pdfs(["= One", "= Two"])
+      id = batch.id
+
+      assert_receive {:ex_typst_batch, ^id, 0, {:ok, _pdf}}, 5_000
+      refute_receive {:ex_typst_batch, ^id, 1, _}, 500
+
+      :ok = ExTypst.Batch.ack(batch)
+      assert_receive {:ex_typst_batch, ^id, 1, {:ok, _pdf}}, 5_000
+      refute_receive {:ex_typst_batch, ^id, :done}, 500
+
+      :ok = ExTypst.Batch.ack(batch)
+      assert_receive {:ex_typst_batch, ^id, :done}, 5_000
+    end
+
+    test "ignores acknowledgments of no waiting document" do
+      {:ok, batch} = ExTypst.stream_pdfs(["= One", "= Two", "= Three"])
+      id = batch.id
+
+      assert_receive {:ex_typst_batch, ^id, 0, {:ok, _pdf}}, 5_000
+      :ok = ExTypst.Batch.ack(batch)
+      :ok = ExTypst.Batch.ack(batch)
+
+      assert_receive {:ex_typst_batch, ^id, 1, {:ok, _pdf}}, 5_000
+      refute_receive {:ex_typst_batch, ^id, 2, _}, 500
+    end
+  end
+
+  describe "render_to_pdf/3 with :encrypt" do
+    test "password-protects the document" do
+      {:ok, plain} = ExTypst.render_to_pdf("Salary: 1000")
+
+      {:ok, encrypted} =
+        ExTypst.render_to_pdf("Salary: 1000", [],
+          encrypt: [owner_password: "owner", user_password: "user", deny: [:print, :copy]]
+        )
+
+      refute plain =~ "/Encrypt"
+      assert encrypted =~ "/Encrypt"
+
+      # Bits 3 and 12 allow printing, bit 5 copying, bit 4 modifying and bit
+      # 6 annotating, counted from 1 as in the PDF reference.
+      [_, p] = Regex.run(~r{/P\s*(-?\d+)}, encrypted)
+      permissions = String.to_integer(p)
+
+      assert Bitwise.band(permissions, 0b1000_0000_0100) == 0
+      assert Bitwise.band(permissions, 0b10000) == 0
+      assert Bitwise.band(permissions, 0b1000) != 0
+      assert Bitwise.band(permissions, 0b100000) != 0
+    end
+
+    test "allows everything not denied" do
+      {:ok, encrypted} =
+        ExTypst.render_to_pdf("Salary: 1000", [], encrypt: [owner_password: "owner"])
+
+      [_, p] = Regex.run(~r{/P\s*(-?\d+)}, encrypted)
+
+      assert Bitwise.band(String.to_integer(p), 0b1111_0011_1100) == 0b1111_0011_1100
+    end
+
+    test "requires an owner password" do
+      assert_raise KeyError, fn ->
+        ExTypst.render_to_pdf("Salary: 1000", [], encrypt: [user_password: "user"])
+      end
+    end
+  end
+
+  describe "cache_stats/1" do
+    @template ~S|#read("fonts/LinLibertine_R.ttf", encoding: none).len()|
+
+    test "keeps files across compilations with a persistent cache" do
+      {:ok, world} = ExTypst.new_world(root: "priv", cache: {:persistent, 60_000})
+
+      for _ <- 1..2 do
+        assert {:ok, _pdf} = ExTypst.render_to_pdf(@template, [], world: world)
+      end
+
+      assert {:ok, %{hits: hits, misses: 1, entries: 1}} = ExTypst.cache_stats(world)
+      assert hits > 0
+    end
+
+    test "reads files on every access without a cache" do
+      {:ok, world} = ExTypst.new_world(root: "priv", cache: :none)
+
+      for _ <- 1..2 do
+        assert {:ok, _pdf} = ExTypst.render_to_pdf(@template, [], world: world)
+      end
+
+      assert {:ok, %{hits: 0, misses: misses, entries: 0}} = ExTypst.cache_stats(world)
+      assert misses >= 2
+    end
+
+    test "doesn't read files outside of the root" do
+      {:ok, world} = ExTypst.new_world(root: "priv")
+
+      assert {:error, _reason} = ExTypst.render_to_pdf(~S|#read("../mix.exs")|, [], world: world)
+    end
+  end
+
+  describe "render_to_pdf/3 with :max_pages" do
+    test "fails documents with more pages" do
+      markup = "#for _ in range(5) { pagebreak(weak: false); [Page] }"
+
+      assert ExTypst.render_to_pdf(markup, [], max_pages: 5) == {:error, {:page_limit, 5}}
+      assert {:ok, _pdf} = ExTypst.render_to_pdf(markup, [], max_pages: 6)
+    end
+
+    test "takes precedence over other checks" do
+      opts = [max_pages: 1, reject_empty: true, require: [text: ["missing"]]]
+
+      assert ExTypst.render_to_pdf("#pagebreak()", [], opts) == {:error, {:page_limit, 1}}
+    end
+
+    test "applies to reports" do
+      markup = "#table([Cell])#pagebreak()#table([Cell])"
+
+      assert ExTypst.tables(markup, [], max_pages: 1) == {:error, {:page_limit, 1}}
+      assert {:ok, [_, _]} = ExTypst.tables(markup, [], max_pages: 2)
+    end
+  end
+
+  describe "render_to_pdf/3 with :expect_uniform_pages" do
+    test "fails documents mixing page sizes" do
+      markup = ~s|A4\n#set page(flipped: true)\nA4 landscape|
+
+      assert {:error, {:mixed_page_sizes, [first, second]}} =
+               ExTypst.render_to_pdf(markup, [], expect_uniform_pages: true)
+
+      assert %{page: 1, orientation: :portrait} = first
+      assert %{page: 2, orientation: :landscape} = second
+      assert {:ok, _pdf} = ExTypst.render_to_pdf(markup)
+    end
+
+    test "accepts documents with pages of one size" do
+      markup = ~s|#set page(paper: "us-letter")\nOne\n#pagebreak()\nTwo|
+
+      assert {:ok, _pdf} = ExTypst.render_to_pdf(markup, [], expect_uniform_pages: true)
+    end
+  end
+
+  describe "render_to_pdf/3 with :assertions" do
+    test "fails with the message and position of a failed assertion" do
+      markup = """
+      #let items = ()
+      #assert-nonempty(items, message: "line items must not be empty")
+      """
+
+      assert ExTypst.render_to_pdf(markup, [], assertions: true) ==
+               {:error,
+                {:assertion_failed,
+                 [
+                   %{
+                     message: "line items must not be empty",
+                     position: %{path: "MARKUP.typ", line: 2, column: 2}
+                   }
+                 ]}}
+    end
+
+    test "describes unequal values" do
+      assert {:error, {:assertion_failed, [%{message: "expected 2 to equal 3"}]}} =
+               ExTypst.render_to_pdf("#assert-eq(1 + 1, 3)", [], assertions: true)
+    end
+
+    test "passes when assertions hold" do
+      markup = ~s|#assert-nonempty("text")\n#assert-eq((a: 1), (a: 1))\nFine|
+
+      assert {:ok, _pdf} = ExTypst.render_to_pdf(markup, [], assertions: true)
+    end
+
+    test "are only available on request" do
+      assert {:error, "compile error:\nunknown variable: assert-eq\n"} =
+               ExTypst.render_to_pdf("#assert-eq(1, 1)")
+    end
+
+    test "are available in imported files" do
+      files = %{
+        "main.typ" => ~s|#import "lib.typ": total\n#total(())|,
+        "lib.typ" => "#let total(items) = {\n  assert-nonempty(items)\n  items.sum()\n}"
+      }
+
+      assert ExTypst.compile_project(files, entry: "main.typ", assertions: true) ==
+               {:error,
+                {:assertion_failed,
+                 [
+                   %{
+                     message: "expected a non-empty value",
+                     position: %{path: "lib.typ", line: 2, column: 3}
+                   }
+                 ]}}
+    end
+
+    test "leave other errors alone" do
+      assert {:error, "compile error:\nequality assertion failed" <> _} =
+               ExTypst.render_to_pdf("#assert.eq(1, 2)", [], assertions: true)
+
+      assert {:error, "compile error:\npanicked with: \"assertion failed\"\n"} =
+               ExTypst.render_to_pdf(~s|#panic("assertion failed")|, [], assertions: true)
+    end
+  end
+
+  describe "render_to_pdf/3 with :debug" do
+    test "logs how files were resolved" do
+      markup = ~s|#read("fonts/LinLibertine_R.ttf", encoding: none).len()\n#read("missing.txt")|
+
+      assert {:error, _reason, log} = ExTypst.render_to_pdf(markup, [], root: "priv", debug: true)
+
+      assert %{kind: :file, outcome: :read, detail: "priv/fonts/LinLibertine_R.ttf"} =
+               Enum.find(log, &(&1.path == "fonts/LinLibertine_R.ttf"))
+
+      assert %{kind: :file, outcome: :failed, detail: "priv/missing.txt" <> _} =
+               Enum.find(log, &(&1.path == "missing.txt"))
+    end
+
+    test "logs which fonts were loaded" do
+      markup = ~s|#set text(font: "Inria Serif")\nHey|
+
+      assert {:ok, _pdf, log} = ExTypst.render_to_pdf(markup, [], debug: true)
+      assert %{outcome: :loaded, path: path} = Enum.find(log, &(&1.detail == "Inria Serif"))
+      assert Path.basename(path) == "InriaSerif-Regular.ttf"
+    end
+
+    test "explains why files can't be read without a root" do
+      markup = ~s|#read("data.txt")|
+
+      assert {:error, _reason, log} = ExTypst.render_to_pdf(markup, [], debug: true)
+
+      assert %{outcome: :failed, detail: "no root" <> _} =
+               Enum.find(log, &(&1.path == "data.txt"))
+    end
+  end
+
+  describe "render_to_pdf/3 with :reject_empty" do
+    test "rejects whitespace-only markup" do
+      assert ExTypst.render_to_pdf(" \n\t", [], reject_empty: true) == {:error, :empty_document}
+      assert {:ok, _pdf} = ExTypst.render_to_pdf(" \n\t")
+    end
+
+    test "rejects documents without visible content" do
+      markup = ~s|#set page(paper: "a5")\n#metadata("total") <total>|
+
+      assert ExTypst.render_to_pdf(markup, [], reject_empty: true) == {:error, :empty_document}
+    end
+
+    test "accepts documents with content" do
+      assert {:ok, _pdf} = ExTypst.render_to_pdf("Invoice", [], reject_empty: true)
+    end
+  end
+
+  describe "render_to_pdf/3 with :require" do
+    @invoice """
+    = Invoice
+
+    #table(columns: 2, [Item], [Price], [Coffee], [3.50])
+
+    Total due: #metadata(3.50) <total> 3.50
+    """
+
+    test "accepts documents meeting all requirements" do
+      requirements = [min_pages: 1, labels: ["total"], text: ["Total due: 3.50", "Coffee"]]
+
+      assert {:ok, _pdf} = ExTypst.render_to_pdf(@invoice, [], require: requirements)
+    end
+
+    test "reports every unmet requirement" do
+      requirements = [min_pages: 2, labels: ["total", "signature"], text: ["Total due", "VAT"]]
+
+      assert ExTypst.render_to_pdf(@invoice, [], require: requirements) ==
+               {:error,
+                {:validation,
+                 [
+                   {:min_pages, 2, 1},
+                   {:missing_label, "signature"},
+                   {:missing_text, "VAT"}
+                 ]}}
+    end
+  end
+
+  describe "supported_features/0" do
+    test "reports the exporters of the default build" do
+      features = ExTypst.supported_features()
+
+      assert features.svg_export
+      assert features.png_export
+      refute features.html_export
+      assert features.pdf_standards == [:pdf_1_7]
+    end
+  end
 end